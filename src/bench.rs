@@ -0,0 +1,153 @@
+//! `ratio bench [size]` compares the .obj parser with the line by line reader it replaced,
+//! on a grid of size x size squares written in the temporary folder (about 200 MB for the default size of 1000).
+
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::str::FromStr;
+use std::time::Instant;
+
+use crate::object::mesh::{Corner, Mesh};
+use crate::object::parser;
+use crate::object::resolver::FileResolver;
+use crate::object::triangulate;
+
+pub fn run(args: &[String]) {
+    let size: usize = args
+        .first()
+        .and_then(|arg| usize::from_str(arg).ok())
+        .unwrap_or(1000);
+    let path = std::env::temp_dir().join(format!("ratio_bench_{}.obj", size));
+    let path = path.to_string_lossy().into_owned();
+    if !std::path::Path::new(&path).exists() {
+        println!("Writing {}", path);
+        if let Err(err) = write_grid(&path, size) {
+            println!("ERROR: {}", err);
+            return;
+        }
+    }
+    let megabytes = std::fs::metadata(&path).map_or(0, |m| m.len()) as f64 / 1e6;
+    println!(
+        "{} ({:.0} MB, {} triangles)",
+        path,
+        megabytes,
+        2 * size * size
+    );
+
+    let start = Instant::now();
+    let baseline = match load_lines(&path) {
+        Ok(mesh) => mesh,
+        Err(err) => {
+            println!("ERROR: {}", err);
+            return;
+        }
+    };
+    report("line by line", start, megabytes);
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut counts = vec![1, threads];
+    counts.dedup();
+    for threads in counts {
+        let start = Instant::now();
+        let data = std::fs::read(&path).unwrap();
+        let mesh = match parser::parse(&data, &path, &FileResolver::next_to(&path), threads) {
+            Ok(parsed) => parsed.mesh,
+            Err(err) => {
+                println!("ERROR: {}", err);
+                return;
+            }
+        };
+        report(&format!("parser, {} threads", threads), start, megabytes);
+        if mesh.positions != baseline.positions
+            || mesh.uvs != baseline.uvs
+            || mesh.normals != baseline.normals
+            || mesh.corners != baseline.corners
+        {
+            println!("ERROR: the meshes are different");
+        }
+    }
+}
+
+fn report(name: &str, start: Instant, megabytes: f64) {
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{:>20}: {:7.3} s, {:7.1} MB/s",
+        name,
+        seconds,
+        megabytes / seconds
+    );
+}
+
+/// A wavy grid with texture coordinates and normals, made of triangles like a scan.
+fn write_grid(path: &str, size: usize) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "# {} x {} grid", size, size)?;
+    for i in 0..=size {
+        for j in 0..=size {
+            let (x, z) = (i as f32 / size as f32, j as f32 / size as f32);
+            let y = 0.05 * (20.0 * x).sin() * (20.0 * z).cos();
+            writeln!(file, "v {:.6} {:.6} {:.6}", x, y, z)?;
+            writeln!(file, "vt {:.6} {:.6}", x, z)?;
+            writeln!(file, "vn {:.6} {:.6} {:.6}", 0.0, 1.0, 0.0)?;
+        }
+    }
+    for i in 0..size {
+        for j in 0..size {
+            let a = i * (size + 1) + j + 1;
+            let (b, c, d) = (a + 1, a + size + 2, a + size + 1);
+            for &(p, q, r) in [(a, b, c), (a, c, d)].iter() {
+                writeln!(
+                    file,
+                    "f {}/{}/{} {}/{}/{} {}/{}/{}",
+                    p, p, p, q, q, q, r, r, r
+                )?;
+            }
+        }
+    }
+    file.flush()
+}
+
+/// The loop obj::load_new used before the parser: a String per line, split_whitespace and f32::from_str.
+/// Only the records written by write_grid are read.
+fn load_lines(path: &str) -> std::io::Result<Mesh> {
+    let reader = std::io::BufReader::new(File::open(path)?);
+    let mut mesh = Mesh::default();
+    for line in reader.lines() {
+        let line = line?;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => {
+                let v: Vec<f32> = words.map(|w| f32::from_str(w).unwrap()).collect();
+                mesh.positions.push([v[0], v[1], v[2]]);
+            }
+            Some("vt") => {
+                let v: Vec<f32> = words.map(|w| f32::from_str(w).unwrap()).collect();
+                mesh.uvs.push([v[0], v[1]]);
+            }
+            Some("vn") => {
+                let v: Vec<f32> = words.map(|w| f32::from_str(w).unwrap()).collect();
+                mesh.normals.push([v[0], v[1], v[2]]);
+            }
+            Some("f") => {
+                let corners: Vec<Corner> = words
+                    .map(|word| {
+                        let mut data = word.split('/').map(|i| i64::from_str(i).unwrap() - 1);
+                        Corner {
+                            position: data.next().unwrap() as u32,
+                            uv: data.next().map(|i| i as u32),
+                            normal: data.next().map(|i| i as u32),
+                        }
+                    })
+                    .collect();
+                let points: Vec<[f32; 3]> = corners
+                    .iter()
+                    .map(|c| mesh.positions[c.position as usize])
+                    .collect();
+                for (a, b, c) in triangulate::triangulate(&points) {
+                    mesh.push_triangle([corners[a], corners[b], corners[c]], 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(mesh)
+}
@@ -44,6 +44,15 @@ impl PerspectiveCamera {
         ]
     }
 
+    /// Size in pixels of a length of one at `point`, in a viewport `viewport_height` pixels high.
+    pub fn pixels_per_unit(&self, point: [f32; 3], viewport_height: f32) -> f32 {
+        let depth = point[2] - self.pos[2];
+        if depth <= 0.0 {
+            return f32::INFINITY;
+        }
+        viewport_height / (2.0 * (self.fov / 2.0).tan() * depth)
+    }
+
     pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
         self.aspect_ratio = aspect_ratio;
     }
@@ -69,6 +78,20 @@ impl PerspectiveCamera {
         self.pos[1] += shift[1];
         self.pos[2] += shift[2];
     }
+
+    /// Move the camera back along its view direction (+z) until the sphere fits in the view,
+    /// with the near and far planes set for the size of the sphere.
+    pub fn frame(&mut self, center: [f32; 3], radius: f32) {
+        // the sphere must fit in the narrowest of the vertical and horizontal fields of view
+        let horizontal_fov = 2.0 * ((self.fov / 2.0).tan() * self.aspect_ratio).atan();
+        let fov = self.fov.min(horizontal_fov);
+        let distance = radius / (fov / 2.0).sin();
+
+        self.pos = [center[0], center[1], center[2] - distance];
+        // far enough apart to zoom in and out a lot (see shift_position) without clipping the model
+        self.near = radius * 1e-3;
+        self.far = (distance + radius) * 100.0;
+    }
 }
 
 impl std::fmt::Display for PerspectiveCamera {
@@ -0,0 +1,91 @@
+//! `ratio check <model> [--repair <output.obj>]` prints the problems found in a model (see object::validate),
+//! with `--repair` it fixes those that can be fixed and saves the result as an .obj file with its materials.
+//! A model named `-` is read from stdin, in the format given by `--format <extension>` (obj by default),
+//! the files it references are looked for in the current folder.
+//! .obj files are always parsed, the binary cache next to them is neither read nor written.
+//! `--simplify <triangles>` also prints how far the model moves when simplified to that many triangles, stopping
+//! earlier when it would move further than `--max-error` (see object::simplify, LOD_MAX_ERROR by default).
+//! The exit code is 1 when problems are left, 2 when the model can't be loaded or saved.
+
+use crate::object;
+use crate::object::resolver::FileResolver;
+use crate::object::simplify::LOD_MAX_ERROR;
+
+const USAGE: &str = "usage: ratio check <model> [--repair <output.obj>] [--format <extension, for - (stdin)>]\n                   [--simplify <triangles>] [--max-error <relative to the model size>]";
+
+pub fn run(args: &[String]) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => exit(USAGE),
+    };
+    let mut output = None;
+    let mut format = "obj";
+    let mut target_triangles = None;
+    let mut target_error = LOD_MAX_ERROR;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        let value = match options.next() {
+            Some(value) => value,
+            None => exit(USAGE),
+        };
+        match option.as_str() {
+            "--repair" => output = Some(value),
+            "--format" => format = value,
+            "--simplify" => match value.parse() {
+                Ok(triangles) => target_triangles = Some(triangles),
+                Err(_) => exit(USAGE),
+            },
+            "--max-error" => match value.parse() {
+                Ok(error) => target_error = error,
+                Err(_) => exit(USAGE),
+            },
+            _ => exit(&format!("ERROR: unknown option '{}'\n{}", option, USAGE)),
+        }
+    }
+
+    let loaded = if path == "-" {
+        let stdin = std::io::stdin();
+        let resolver = FileResolver {
+            folder: std::path::PathBuf::new(),
+        };
+        let name = format!("stdin.{}", format);
+        object::obj::from_reader(stdin.lock(), &name, &resolver, true, true, false, true)
+    } else if path.to_lowercase().ends_with(".obj") {
+        // the file itself, without reading or writing the cache next to it
+        object::obj::load_new(path, true, true, false, true).map(|mut obj| {
+            obj.build_indices();
+            obj
+        })
+    } else {
+        object::obj::load(path, true, true, false, true)
+    };
+    let mut obj = loaded.unwrap_or_else(|err| exit(&format!("ERROR: {}", err)));
+    let mut report = obj.validate();
+    println!("{}: {}", path, report);
+
+    if let Some(target_triangles) = target_triangles {
+        if let Some(lod) = obj.simplify(target_triangles, target_error) {
+            println!(
+                "SIMPLIFIED: {} triangles, error {}",
+                lod.triangles, lod.error
+            );
+        }
+    }
+
+    if let Some(output) = output {
+        println!("REPAIRED: {}", obj.repair());
+        if let Err(err) = obj.save(output, true, false) {
+            exit(&format!("ERROR: could not save '{}': {}", output, err));
+        }
+        report = obj.validate();
+        println!("{}: {}", output, report);
+    }
+    if !report.is_clean() {
+        std::process::exit(1);
+    }
+}
+
+fn exit(message: &str) -> ! {
+    println!("{}", message);
+    std::process::exit(2);
+}
@@ -4,7 +4,9 @@ use glutin::window::WindowBuilder;
 use glutin::ContextBuilder;
 
 mod opengl;
-use opengl::buffers::{AttributeType, FrameBuffer, RenderBuffer, VertexBuffer, VertexBufferLayout};
+use opengl::buffers::{
+    AttributeType, FrameBuffer, IndexBuffer, RenderBuffer, VertexBuffer, VertexBufferLayout,
+};
 use opengl::shaders::{Program, Shader};
 use opengl::textures::{Texture, TextureDataType};
 use opengl::uniforms::{Uniform, UniformType};
@@ -14,19 +16,47 @@ mod camera;
 use camera::PerspectiveCamera;
 
 mod object;
+use object::bounds::BoundingSphere;
+use object::normals::NormalMode;
+use object::primitives::Shape;
+use object::simplify::Lod;
+use object::tangents::TangentMode;
+use object::uv::Projection;
+use object::transform::{Handedness, ImportTransform, UpAxis};
+
+mod bench;
+
+mod check;
+
+mod material;
+use material::MaterialLibrary;
 
 // https://github.com/LordBenjamin/sharp-and-rusty
 // https://github.com/gobanos/test-glutin-opengl/blob/master/src/main.rs
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("bench") {
+        bench::run(&args[2..]);
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("check") {
+        check::run(&args[2..]);
+        return;
+    }
+
     // load model
 
-    let mut obj = object::obj::load_new(
-        "D:/Davide/Programmazione/Rust/ratio/src/image_source/suzanne.obj",
-        true,
-        true,
-        true,
-    );
+    let (path_arg, import) = viewer_options(&args[1..]);
+    let mut model_path = path_arg.unwrap_or_else(|| {
+        String::from("D:/Davide/Programmazione/Rust/ratio/src/image_source/suzanne.obj")
+    });
+    let mut obj = load_model(&model_path, &import).unwrap_or_else(|err| {
+        println!("ERROR: {}", err);
+        object::obj::empty()
+    });
+    print_parts(&obj);
+    print_problems(&obj);
 
     // init window
 
@@ -49,20 +79,6 @@ fn main() {
     let vb = VertexBuffer::new(obj.get_vertices(), gl);
     vb.bind(gl);
 
-    let vbl = VertexBufferLayout::new(
-        vec![
-            (String::from("a_Position"), AttributeType::Float3),
-            (String::from("a_TexCoords"), AttributeType::Float2),
-            (String::from("a_Normal"), AttributeType::Float3),
-            (String::from("a_Tangent"), AttributeType::Float3),
-            (String::from("a_Bitangent"), AttributeType::Float3),
-        ],
-        gl,
-    );
-    vbl.bind(gl);
-
-    glwr.print_errors();
-
     let shader = Shader::new(
         include_str!("shader_source/BSDF.vertex"),
         include_str!("shader_source/BSDF.fragment"),
@@ -72,9 +88,34 @@ fn main() {
     program.bind(gl);
     // shader.delete(gl);
 
+    let mut vbl = model_layout(&obj, &program, gl);
+    vbl.bind(gl);
+
+    // the index buffer is bound to the vertex array of vbl
+    let mut ib = IndexBuffer::new(obj.get_indices(), gl);
+    ib.bind(gl);
+
+    glwr.print_errors();
+
     let mut camera = PerspectiveCamera::new([0.0, 0.0, -5.0]);
     let (vw, vh): (f32, f32) = windowed_context.window().inner_size().into();
     camera.set_aspect_ratio(vw / vh);
+    // camera moves are relative to the size of the model
+    let mut model_size = frame_model(&obj, &mut camera);
+    let mut viewport_height = vh;
+
+    // simplified index buffers drawn when the model is small on screen, made with L
+    let mut lods: Vec<(Lod, IndexBuffer)> = Vec::new();
+    let mut lod_sphere = obj.bounding_sphere();
+    let mut current_lod = None;
+    // subdivision levels shown, changed with S, and how long creases stay sharp, changed with C
+    let mut subdivision_levels = 0;
+    let mut crease_sharpness = CREASE_SHARPNESS[0];
+    // texture coordinates generated with U for models without them
+    let mut file_has_uvs = obj.has_texture_coordinates();
+    let mut projection = None;
+    // generated shape shown instead of a model, changed with P
+    let mut shape: Option<Shape> = None;
     let mut vp_matrix = Uniform::new(
         "vp_matrix",
         UniformType::Mat4x4(camera.matrix()),
@@ -82,41 +123,25 @@ fn main() {
         gl,
     );
 
-    let mut texture_diffuse = Texture::load_new(
-        "D:/Davide/Programmazione/Rust/ratio/src/image_source/diffuse.jpg",
-        0,
-        gl,
-    );
-    texture_diffuse.bind(gl);
+    // textures of the materials referenced by the model
+    let mut materials = MaterialLibrary::new(&obj, gl);
     let mut uniform_diffuse = Uniform::new(
         "diffuse_map",
-        UniformType::Texture(texture_diffuse.get_id()),
+        UniformType::Texture(material::DIFFUSE_UNIT),
         &program,
         gl,
     );
-
-    let mut texture_normal = Texture::load_new(
-        "D:/Davide/Programmazione/Rust/ratio/src/image_source/normal.jpg",
-        1,
-        gl,
-    );
-    texture_normal.bind(gl);
     let mut uniform_normal = Uniform::new(
         "normal_map",
-        UniformType::Texture(texture_normal.get_id()),
+        UniformType::Texture(material::NORMAL_UNIT),
         &program,
         gl,
     );
-
-    let mut texture_spec = Texture::load_new(
-        "D:/Davide/Programmazione/Rust/ratio/src/image_source/specularity.jpg",
-        2,
-        gl,
-    );
-    texture_spec.bind(gl);
+    let mut uniform_replace_diffuse =
+        Uniform::new("replace_diffuse", UniformType::Bool(false), &program, gl);
     let mut uniform_spec = Uniform::new(
         "specularity_map",
-        UniformType::Texture(texture_spec.get_id()),
+        UniformType::Texture(material::SPECULARITY_UNIT),
         &program,
         gl,
     );
@@ -174,10 +199,9 @@ fn main() {
     // prepare for render pass
     vb.bind(&glwr.gl);
     vbl.bind(&glwr.gl);
+    ib.bind(&glwr.gl);
     program.bind(&glwr.gl);
     fb.bind(&glwr.gl);
-    texture_diffuse.bind(&glwr.gl);
-    texture_normal.bind(&glwr.gl);
 
     // event loop
 
@@ -195,6 +219,7 @@ fn main() {
 
                     /* UPDATE CAMERA */
                     camera.set_aspect_ratio((width as f32) / (height as f32));
+                    viewport_height = height as f32;
                     vp_matrix.set(UniformType::Mat4x4(camera.matrix()), &program, &glwr.gl);
 
                     /* UPDATE VIEPORT */
@@ -202,7 +227,6 @@ fn main() {
 
                     /* RESIZE THE FRAME BUFFER TEXTURE AND THE RENDER BUFFER */
                     fb.resize_texture(width as usize, height as usize, &glwr.gl);
-                    texture_diffuse.bind(&glwr.gl); // back to the right texture
                     rb.resize(width as usize, height as usize, &glwr.gl);
                 }
                 WindowEvent::MouseWheel {
@@ -212,13 +236,13 @@ fn main() {
                     modifiers: _,
                 } => match delta {
                     glutin::event::MouseScrollDelta::LineDelta(_dx, dy) => {
-                        camera.shift_position([0.0, 0.0, dy / 5.0]);
+                        camera.shift_position([0.0, 0.0, dy / 5.0 * model_size]);
                         vp_matrix.set(UniformType::Mat4x4(camera.matrix()), &program, &glwr.gl);
                         windowed_context.window().request_redraw();
                     }
                     glutin::event::MouseScrollDelta::PixelDelta(p) => {
                         let glutin::dpi::LogicalPosition { x: _x, y } = p;
-                        camera.shift_position([0.0, 0.0, (y as f32) / 200.0]);
+                        camera.shift_position([0.0, 0.0, (y as f32) / 200.0 * model_size]);
                         vp_matrix.set(UniformType::Mat4x4(camera.matrix()), &program, &glwr.gl);
                         windowed_context.window().request_redraw();
                     }
@@ -231,7 +255,7 @@ fn main() {
                     if let Some(key_code) = input.virtual_keycode {
                         match key_code {
                             glutin::event::VirtualKeyCode::A => {
-                                camera.shift_position([-0.1, 0.0, 0.0]);
+                                camera.shift_position([-0.1 * model_size, 0.0, 0.0]);
                                 vp_matrix.set(
                                     UniformType::Mat4x4(camera.matrix()),
                                     &program,
@@ -240,7 +264,7 @@ fn main() {
                                 windowed_context.window().request_redraw();
                             }
                             glutin::event::VirtualKeyCode::D => {
-                                camera.shift_position([0.1, 0.0, 0.0]);
+                                camera.shift_position([0.1 * model_size, 0.0, 0.0]);
                                 vp_matrix.set(
                                     UniformType::Mat4x4(camera.matrix()),
                                     &program,
@@ -248,7 +272,224 @@ fn main() {
                                 );
                                 windowed_context.window().request_redraw();
                             }
-                            _ => (),
+                            // EXPORT THE MODEL NEXT TO THE LOADED FILE
+                            glutin::event::VirtualKeyCode::E => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let path = export_path(&model_path);
+                                    match obj.save(&path, true, false) {
+                                        Ok(()) => println!("SAVED: {}", path),
+                                        Err(err) => {
+                                            println!("ERROR: could not save '{}': {}", path, err)
+                                        }
+                                    }
+                                }
+                            }
+                            // FRAME THE VISIBLE PARTS
+                            glutin::event::VirtualKeyCode::F => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    model_size = frame_model(&obj, &mut camera);
+                                    vp_matrix.set(
+                                        UniformType::Mat4x4(camera.matrix()),
+                                        &program,
+                                        &glwr.gl,
+                                    );
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // VALIDATE AND REPAIR THE MESH
+                            glutin::event::VirtualKeyCode::V => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    println!("VALIDATION: {}", obj.validate());
+                                }
+                            }
+                            glutin::event::VirtualKeyCode::R => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    println!("REPAIRED: {}", obj.repair());
+                                    // a subdivided model keeps its subdivision as its new cage
+                                    subdivision_levels = 0;
+                                    print_parts(&obj);
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // MAKE OR REMOVE THE LEVELS OF DETAIL
+                            glutin::event::VirtualKeyCode::L => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    if lods.is_empty() {
+                                        for (i, lod) in obj.lod_chain().into_iter().enumerate() {
+                                            println!(
+                                                "LOD {}: {} triangles, error {}",
+                                                i + 1,
+                                                lod.triangles,
+                                                lod.error
+                                            );
+                                            let lod_ib = IndexBuffer::new(&lod.indices, &glwr.gl);
+                                            lods.push((lod, lod_ib));
+                                        }
+                                        lod_sphere = obj.bounding_sphere();
+                                        // creating the buffers bound them to the vertex array
+                                        ib.bind(&glwr.gl);
+                                    } else {
+                                        delete_lods(&mut lods, &glwr.gl);
+                                        println!("LOD: off");
+                                    }
+                                    current_lod = None;
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // SUBDIVIDE THE MODEL, UP TO MAX_SUBDIVISION_LEVELS THEN BACK TO THE CAGE
+                            glutin::event::VirtualKeyCode::S => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    subdivision_levels =
+                                        (subdivision_levels + 1) % (MAX_SUBDIVISION_LEVELS + 1);
+                                    subdivide(&mut obj, subdivision_levels, crease_sharpness);
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // ROUND OFF THE CREASES OF THE SUBDIVISION AFTER MORE OR LESS LEVELS
+                            glutin::event::VirtualKeyCode::C => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let next = CREASE_SHARPNESS
+                                        .iter()
+                                        .position(|&s| s == crease_sharpness)
+                                        .map_or(0, |i| (i + 1) % CREASE_SHARPNESS.len());
+                                    crease_sharpness = CREASE_SHARPNESS[next];
+                                    if subdivision_levels == 0 {
+                                        let name = crease_sharpness_name(crease_sharpness);
+                                        println!("CREASES: {}", name);
+                                    } else {
+                                        subdivide(&mut obj, subdivision_levels, crease_sharpness);
+                                        vb.update_data(obj.get_vertices(), &glwr.gl);
+                                        ib.update_data(obj.get_indices(), &glwr.gl);
+                                        delete_lods(&mut lods, &glwr.gl);
+                                        windowed_context.window().request_redraw();
+                                    }
+                                }
+                            }
+                            // GENERATE TEXTURE COORDINATES, WITH THE NEXT PROJECTION EACH TIME
+                            glutin::event::VirtualKeyCode::U => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    if file_has_uvs {
+                                        println!("UV: the model has its own texture coordinates");
+                                    } else {
+                                        let next = next_projection(projection);
+                                        obj.generate_uvs(next);
+                                        projection = Some(next);
+                                        println!("UV: {:?}", next);
+                                        vb.update_data(obj.get_vertices(), &glwr.gl);
+                                        ib.update_data(obj.get_indices(), &glwr.gl);
+                                        delete_lods(&mut lods, &glwr.gl);
+                                        windowed_context.window().request_redraw();
+                                    }
+                                }
+                            }
+                            // PREVIEW THE MATERIALS ON THE NEXT GENERATED SHAPE
+                            glutin::event::VirtualKeyCode::P => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let next = next_shape(shape);
+                                    println!("SHAPE: {}", next.name());
+                                    obj = object::obj::primitive(next, true, true, true, true);
+                                    shape = Some(next);
+                                    model_path = format!("{}.obj", next.name());
+                                    subdivision_levels = 0;
+                                    file_has_uvs = true;
+                                    projection = None;
+                                    print_parts(&obj);
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    vbl.delete(&glwr.gl);
+                                    vbl = model_layout(&obj, &program, &glwr.gl);
+                                    vbl.bind(&glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    ib.bind(&glwr.gl);
+                                    // the textures dropped on the previous model stay, shapes have no materials
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    model_size = frame_model(&obj, &mut camera);
+                                    vp_matrix.set(UniformType::Mat4x4(camera.matrix()), &program, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // SWITCH BETWEEN FILE, FLAT AND SMOOTH NORMALS
+                            glutin::event::VirtualKeyCode::N => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let mode = match obj.get_normal_mode() {
+                                        NormalMode::File => NormalMode::Flat,
+                                        NormalMode::Flat => NormalMode::Smooth,
+                                        NormalMode::Smooth => NormalMode::File,
+                                    };
+                                    obj.set_normal_mode(mode);
+                                    print_normals(&obj);
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // SWITCH BETWEEN SMOOTH AND MIKKTSPACE TANGENTS
+                            glutin::event::VirtualKeyCode::T => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let mode = match obj.get_tangent_mode() {
+                                        TangentMode::Smooth => TangentMode::MikkTSpace,
+                                        TangentMode::MikkTSpace => TangentMode::Smooth,
+                                    };
+                                    obj.set_tangent_mode(mode);
+                                    println!("TANGENTS: {:?}", mode);
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // CHANGE THE CREASE ANGLE OF GENERATED NORMALS
+                            glutin::event::VirtualKeyCode::LBracket
+                            | glutin::event::VirtualKeyCode::RBracket => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    let step = if key_code == glutin::event::VirtualKeyCode::RBracket {
+                                        CREASE_ANGLE_STEP
+                                    } else {
+                                        -CREASE_ANGLE_STEP
+                                    };
+                                    // no crease angle is the same as the largest one
+                                    let angle = obj.get_crease_angle().unwrap_or(180.0) + step;
+                                    let angle = angle.clamp(CREASE_ANGLE_STEP, 180.0);
+                                    obj.set_crease_angle(if angle < 180.0 { Some(angle) } else { None });
+                                    print_normals(&obj);
+                                    // the creases of the subdivision follow the crease angle
+                                    if subdivision_levels > 0 {
+                                        subdivide(&mut obj, subdivision_levels, crease_sharpness);
+                                    }
+                                    vb.update_data(obj.get_vertices(), &glwr.gl);
+                                    ib.update_data(obj.get_indices(), &glwr.gl);
+                                    delete_lods(&mut lods, &glwr.gl);
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            // SHOW AND HIDE PARTS
+                            glutin::event::VirtualKeyCode::Key0 => {
+                                if input.state == glutin::event::ElementState::Pressed {
+                                    for name in obj.get_part_names() {
+                                        obj.set_part_visible(&name, true);
+                                    }
+                                    windowed_context.window().request_redraw();
+                                }
+                            }
+                            _ => {
+                                if let Some(part) = part_number(key_code) {
+                                    if input.state == glutin::event::ElementState::Pressed {
+                                        if let Some(name) = obj.get_part_names().get(part) {
+                                            let visible = !obj.is_part_visible(name);
+                                            obj.set_part_visible(name, visible);
+                                            let action = if visible { "SHOW" } else { "HIDE" };
+                                            println!("{} PART: {}", action, name);
+                                            windowed_context.window().request_redraw();
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
                     //
@@ -266,13 +507,20 @@ fn main() {
                                 | file.ends_with(".hdr")
                             {
                                 let lower_file = file.to_lowercase();
+                                if !obj.has_texture_coordinates() {
+                                    println!("WARN: the model has no texture coordinates, generate them with U");
+                                }
                                 if lower_file.contains("norm") || lower_file.contains("nrm") {
                                     println!("NORMAL MAP: {}", file);
-                                    glwr.change_texture(&mut texture_normal, &mut uniform_normal, file, &program);
+                                    for textures in materials.iter_mut() {
+                                        glwr.change_texture(&mut textures.normal, &mut uniform_normal, file, &program);
+                                    }
 
                                 }else if lower_file.contains("spec") {
                                     println!("SPECULARITY MAP: {}", file);
-                                    glwr.change_texture(&mut texture_spec, &mut uniform_spec, file, &program);
+                                    for textures in materials.iter_mut() {
+                                        glwr.change_texture(&mut textures.specularity, &mut uniform_spec, file, &program);
+                                    }
 
                                 }else if lower_file.contains("hdr") || lower_file.contains("env") || lower_file.contains("ambient") {
                                     println!("ENVIRONMENT MAP: {}", file);
@@ -280,14 +528,43 @@ fn main() {
 
                                 }else{
                                     println!("DIFFUSE MAP: {}", file);
-                                    glwr.change_texture(&mut texture_diffuse, &mut uniform_diffuse, file, &program);
+                                    for textures in materials.iter_mut() {
+                                        glwr.change_texture(&mut textures.diffuse, &mut uniform_diffuse, file, &program);
+                                        textures.has_diffuse_map = true;
+                                    }
                                 }
                                 windowed_context.window().request_redraw();
-                            } else if file.ends_with(".obj") {
-                                println!("OBJ: {}", file);
-                                obj = object::obj::load_new(file, true, true, true);
-                                vb.update_data(obj.get_vertices(), &glwr.gl);
-                                windowed_context.window().request_redraw();
+                            } else if object::is_model(file) {
+                                println!("MODEL: {}", file);
+                                match load_model(file, &import) {
+                                    Ok(new_obj) => {
+                                        obj = new_obj;
+                                        model_path = String::from(file);
+                                        subdivision_levels = 0;
+                                        file_has_uvs = obj.has_texture_coordinates();
+                                        projection = None;
+                                        shape = None;
+                                        print_parts(&obj);
+                                        print_problems(&obj);
+                                        vb.update_data(obj.get_vertices(), &glwr.gl);
+                                        // the new model may have other attributes
+                                        vbl.delete(&glwr.gl);
+                                        vbl = model_layout(&obj, &program, &glwr.gl);
+                                        vbl.bind(&glwr.gl);
+                                        ib.update_data(obj.get_indices(), &glwr.gl);
+                                        ib.bind(&glwr.gl);
+                                        materials.delete(&glwr.gl);
+                                        materials = MaterialLibrary::new(&obj, &glwr.gl);
+                                        delete_lods(&mut lods, &glwr.gl);
+                                        model_size = frame_model(&obj, &mut camera);
+                                        vp_matrix.set(UniformType::Mat4x4(camera.matrix()), &program, &glwr.gl);
+                                        windowed_context.window().request_redraw();
+                                    }
+                                    Err(err) => {
+                                        // keep the previous mesh on screen
+                                        println!("ERROR: {}", err);
+                                    }
+                                }
                             } else {
                                 println!("WARN: this file is not supported. '{}'", file);                                
                             }
@@ -301,6 +578,9 @@ fn main() {
                 WindowEvent::CloseRequested => {
                     &vbl.delete(&glwr.gl);
                     &vb.delete(&glwr.gl);
+                    ib.delete(&glwr.gl);
+                    delete_lods(&mut lods, &glwr.gl);
+                    materials.delete(&glwr.gl);
                     &program.delete(&glwr.gl);
                     *control_flow = ControlFlow::Exit;
                 }
@@ -312,7 +592,35 @@ fn main() {
                 glwr.draw_frame([0.05, 0.05, 0.05, 1.0]);
                 glwr.clear_depth_buffer();
                 glwr.depth_test(true);
-                glwr.draw_triangles(obj.get_vertices_count());
+                let level = choose_lod(&lods, lod_sphere.as_ref(), &camera, viewport_height);
+                if level != current_lod {
+                    match level {
+                        Some(i) => println!("LOD {}: {} triangles", i + 1, lods[i].0.triangles),
+                        None => println!("LOD 0: full model"),
+                    }
+                    current_lod = level;
+                }
+                if let Some(i) = level {
+                    lods[i].1.bind(&glwr.gl);
+                }
+                for (s, submesh) in obj.get_submeshes().iter().enumerate().filter(|(_, s)| s.visible) {
+                    let textures = materials.get(submesh.material);
+                    textures.bind(&glwr.gl);
+                    uniform_replace_diffuse.set(
+                        UniformType::Bool(obj.has_colors() && !textures.has_diffuse_map),
+                        &program,
+                        &glwr.gl,
+                    );
+                    let (start, count) = match level {
+                        Some(i) => lods[i].0.ranges[s],
+                        None => (submesh.start, submesh.count),
+                    };
+                    let mode = match submesh.primitive {
+                        object::Primitive::Triangles => opengl::gl::TRIANGLES,
+                        object::Primitive::Points => opengl::gl::POINTS,
+                    };
+                    glwr.draw_elements_range(mode, start, count);
+                }
 
                 // post-processing pass
                 vb_screen.bind(&glwr.gl);
@@ -330,12 +638,232 @@ fn main() {
                 // prepare for render pass
                 vb.bind(&glwr.gl);
                 vbl.bind(&glwr.gl);
+                ib.bind(&glwr.gl);
                 program.bind(&glwr.gl);
                 fb.bind(&glwr.gl);
-                texture_diffuse.bind(&glwr.gl);
-                texture_normal.bind(&glwr.gl);
             }
             _ => (),
         }
     });
 }
+
+/// Degrees added to or removed from the crease angle with ] and [.
+const CREASE_ANGLE_STEP: f32 = 15.0;
+
+/// Subdivision levels shown with S, each one has four times the faces of the previous one.
+const MAX_SUBDIVISION_LEVELS: u32 = 3;
+
+/// Levels of subdivision creases stay sharp for, chosen with C.
+const CREASE_SHARPNESS: [f32; 4] = [f32::INFINITY, 1.0, 2.0, 3.0];
+
+const VIEWER_USAGE: &str =
+    "usage: ratio [<model>] [--z-up] [--left-handed] [--recenter] [--unit-size]\n       ratio check ...\n       ratio bench ...";
+
+/// The model to show and how to convert the models loaded by the viewer.
+fn viewer_options(args: &[String]) -> (Option<String>, ImportTransform) {
+    let mut path = None;
+    let mut import = ImportTransform::default();
+    for arg in args {
+        match arg.as_str() {
+            "--z-up" => import.up = UpAxis::Z,
+            "--left-handed" => import.handedness = Handedness::Left,
+            "--recenter" => import.recenter = true,
+            "--unit-size" => import.unit_size = true,
+            option if option.starts_with("--") => {
+                println!("ERROR: unknown option '{}'\n{}", option, VIEWER_USAGE);
+                std::process::exit(2);
+            }
+            _ => path = Some(arg.clone()),
+        }
+    }
+    (path, import)
+}
+
+/// Load a model in the viewer, converted as the command line asked.
+fn load_model(
+    path: &str,
+    import: &ImportTransform,
+) -> Result<object::obj, object::error::ObjError> {
+    let mut obj = object::obj::load(path, true, true, true, true)?;
+    obj.apply_import(import);
+    Ok(obj)
+}
+
+/// The vertex array reading the vertices of `obj` with the inputs of `program`.
+/// The VertexBuffer holding the vertices must be bound.
+fn model_layout(obj: &object::obj, program: &Program, gl: &opengl::gl::Gl) -> VertexBufferLayout {
+    let layout = obj.layout();
+    let attributes = layout
+        .attributes
+        .iter()
+        .map(|a| {
+            let attribute_type = match a.components {
+                1 => AttributeType::Float,
+                2 => AttributeType::Float2,
+                3 => AttributeType::Float3,
+                _ => AttributeType::Float4,
+            };
+            (String::from(a.name), attribute_type, a.offset)
+        })
+        .collect();
+    let defaults: Vec<(String, [f32; 4])> = layout
+        .missing()
+        .into_iter()
+        .map(|(name, value)| (String::from(name), value))
+        .collect();
+    VertexBufferLayout::for_program(attributes, layout.stride, &defaults, program, gl)
+}
+
+/// The coarsest level of detail whose error is under a pixel on screen, None for the full model.
+fn choose_lod(
+    lods: &[(Lod, IndexBuffer)],
+    sphere: Option<&BoundingSphere>,
+    camera: &PerspectiveCamera,
+    viewport_height: f32,
+) -> Option<usize> {
+    let sphere = sphere?;
+    // the errors are relative to the model size, measured where the model is closest to the camera
+    let nearest = [sphere.center[0], sphere.center[1], sphere.center[2] - sphere.radius];
+    let pixels = 2.0 * sphere.radius * camera.pixels_per_unit(nearest, viewport_height);
+    lods.iter().rposition(|(lod, _)| lod.error * pixels <= 1.0)
+}
+
+fn delete_lods(lods: &mut Vec<(Lod, IndexBuffer)>, gl: &opengl::gl::Gl) {
+    for (_, lod_ib) in lods.drain(..) {
+        lod_ib.delete(gl);
+    }
+}
+
+/// Place the camera so that the visible parts of the model fill the view.
+/// Returns the radius of the parts, the scale of the camera moves.
+fn frame_model(obj: &object::obj, camera: &mut PerspectiveCamera) -> f32 {
+    match obj.bounding_sphere() {
+        Some(sphere) => {
+            // a single point has no size
+            let radius = if sphere.radius > 0.0 { sphere.radius } else { 1.0 };
+            camera.frame(sphere.center, radius);
+            radius
+        }
+        None => 1.0,
+    }
+}
+
+/// Print the size of the model and list its parts with the key that toggles them.
+fn print_parts(obj: &object::obj) {
+    if let Some(bounds) = obj.bounds() {
+        let [x, y, z] = bounds.size();
+        println!("SIZE: {} x {} x {}", x, y, z);
+    }
+    for (i, name) in obj.get_part_names().iter().enumerate() {
+        if i < 9 {
+            println!("PART [{}]: {}", i + 1, name);
+        } else {
+            println!("PART: {}", name);
+        }
+    }
+}
+
+/// `model.ext` is exported as `model_export.obj` whatever its format, so that the export never replaces the
+/// .obj and .mtl files of another model next to it.
+fn export_path(model_path: &str) -> String {
+    let path = std::path::Path::new(model_path);
+    let stem = path
+        .file_stem()
+        .map_or(String::from("model"), |s| s.to_string_lossy().into_owned());
+    path.with_file_name(format!("{}_export.obj", stem))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The projection used after `current` by U, starting with the unwrap that suits most models.
+fn next_projection(current: Option<Projection>) -> Projection {
+    match current {
+        None => Projection::Unwrap,
+        Some(Projection::Unwrap) => Projection::Planar,
+        Some(Projection::Planar) => Projection::Cylindrical,
+        Some(Projection::Cylindrical) => Projection::Spherical,
+        Some(Projection::Spherical) => Projection::Box,
+        Some(Projection::Box) => Projection::Unwrap,
+    }
+}
+
+/// The shape shown after `current` by P, going through all of them.
+fn next_shape(current: Option<Shape>) -> Shape {
+    match current.and_then(|shape| Shape::ALL.iter().position(|&s| s == shape)) {
+        Some(i) => Shape::ALL[(i + 1) % Shape::ALL.len()],
+        None => Shape::ALL[0],
+    }
+}
+
+/// Print where the normals come from and the crease angle of generated ones.
+/// Show the model subdivided `levels` times with creases at the crease angle of its normals, or the model as it
+/// was loaded for 0 levels.
+fn subdivide(obj: &mut object::obj, levels: u32, crease_sharpness: f32) {
+    if levels == 0 {
+        obj.set_subdivision(None);
+        println!("SUBDIVISION: off");
+        return;
+    }
+    let mut subdivision = obj.subdivision_for(levels);
+    subdivision.crease_sharpness = crease_sharpness;
+    obj.set_subdivision(Some(&subdivision));
+    match obj.get_crease_angle() {
+        Some(angle) => println!(
+            "SUBDIVISION: {:?}, level {}, creases at {} degrees {}",
+            subdivision.scheme,
+            levels,
+            angle.round(),
+            crease_sharpness_name(crease_sharpness)
+        ),
+        None => println!(
+            "SUBDIVISION: {:?}, level {}, no creases",
+            subdivision.scheme, levels
+        ),
+    }
+}
+
+fn crease_sharpness_name(crease_sharpness: f32) -> String {
+    if crease_sharpness.is_finite() {
+        format!("sharp for {} levels", crease_sharpness)
+    } else {
+        String::from("always sharp")
+    }
+}
+
+fn print_normals(obj: &object::obj) {
+    let mode = match obj.get_normal_mode() {
+        NormalMode::File => "from the file",
+        NormalMode::Flat => "flat",
+        NormalMode::Smooth => "smooth",
+    };
+    match obj.get_crease_angle() {
+        Some(angle) => println!("NORMALS: {}, crease angle {}", mode, angle.round()),
+        None => println!("NORMALS: {}, no crease angle", mode),
+    }
+}
+
+/// Print what validation found in the model, when there is something to repair.
+fn print_problems(obj: &object::obj) {
+    let report = obj.validate();
+    if !report.is_clean() {
+        println!("VALIDATION: {}", report);
+        println!("VALIDATION: press R to repair");
+    }
+}
+
+/// Keys from 1 to 9 toggle the visibility of the first nine parts.
+fn part_number(key_code: glutin::event::VirtualKeyCode) -> Option<usize> {
+    use glutin::event::VirtualKeyCode::*;
+    match key_code {
+        Key1 => Some(0),
+        Key2 => Some(1),
+        Key3 => Some(2),
+        Key4 => Some(3),
+        Key5 => Some(4),
+        Key6 => Some(5),
+        Key7 => Some(6),
+        Key8 => Some(7),
+        Key9 => Some(8),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+use crate::object::mtl::{Material, TextureMap};
+use crate::object::obj;
+use crate::opengl::gl;
+use crate::opengl::textures::{Texture, TextureColorFormat, TextureDataType};
+
+// texture units read by BSDF.fragment
+pub const DIFFUSE_UNIT: u32 = 0;
+pub const NORMAL_UNIT: u32 = 1;
+pub const SPECULARITY_UNIT: u32 = 2;
+
+/// The textures used to draw a material.
+/// Maps missing from the .mtl file are replaced by 1x1 textures holding the material's constant values.
+pub struct MaterialTextures {
+    pub diffuse: Texture,
+    /// false when `diffuse` only holds the diffuse color, so that vertex colors can replace it
+    pub has_diffuse_map: bool,
+    pub normal: Texture,
+    pub specularity: Texture,
+}
+impl MaterialTextures {
+    pub fn new(material: Option<&Material>, gl: &gl::Gl) -> MaterialTextures {
+        let default = Material::new("default");
+        let material = material.unwrap_or(&default);
+
+        // BSDF.fragment reads the specularity as an exponent from 0 to 1000, like Ns
+        let specularity = (material.shininess / 1000.0).max(0.0).min(1.0);
+
+        MaterialTextures {
+            diffuse: load_or_color(&material.diffuse_map, DIFFUSE_UNIT, material.diffuse, gl),
+            has_diffuse_map: material.diffuse_map.is_some(),
+            normal: load_or_color(&material.normal_map, NORMAL_UNIT, [0.5, 0.5, 1.0], gl),
+            specularity: load_or_color(
+                &material.specular_map,
+                SPECULARITY_UNIT,
+                [specularity, specularity, specularity],
+                gl,
+            ),
+        }
+    }
+
+    pub fn bind(&self, gl: &gl::Gl) {
+        self.diffuse.bind(gl);
+        self.normal.bind(gl);
+        self.specularity.bind(gl);
+    }
+
+    pub fn delete(&self, gl: &gl::Gl) {
+        self.diffuse.delete(gl);
+        self.normal.delete(gl);
+        self.specularity.delete(gl);
+    }
+}
+
+/// MaterialTextures for all the materials of an obj, plus the ones used by submeshes without a material.
+pub struct MaterialLibrary {
+    materials: Vec<MaterialTextures>,
+    default: MaterialTextures,
+}
+impl MaterialLibrary {
+    pub fn new(obj: &obj, gl: &gl::Gl) -> MaterialLibrary {
+        MaterialLibrary {
+            materials: obj
+                .get_materials()
+                .iter()
+                .map(|material| MaterialTextures::new(Some(material), gl))
+                .collect(),
+            default: MaterialTextures::new(None, gl),
+        }
+    }
+
+    pub fn get(&self, material: Option<usize>) -> &MaterialTextures {
+        material
+            .and_then(|i| self.materials.get(i))
+            .unwrap_or(&self.default)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut MaterialTextures> {
+        self.materials
+            .iter_mut()
+            .chain(std::iter::once(&mut self.default))
+    }
+
+    pub fn delete(&self, gl: &gl::Gl) {
+        for material in self.materials.iter() {
+            material.delete(gl);
+        }
+        self.default.delete(gl);
+    }
+}
+
+fn load_or_color(map: &Option<TextureMap>, unit: u32, color: [f32; 3], gl: &gl::Gl) -> Texture {
+    if let Some(map) = map {
+        let path = map.path.to_string_lossy();
+        let texture = match &map.data {
+            Some(data) => Texture::try_load_from_memory(data, unit, gl),
+            None => Texture::try_load_new(&path, unit, gl),
+        };
+        match texture {
+            Ok(texture) => return texture,
+            Err(err) => println!("WARN: could not load texture '{}': {}", path, err),
+        }
+    }
+    Texture::new(
+        unit,
+        1,
+        1,
+        Some(color.to_vec()),
+        TextureColorFormat::RGB,
+        TextureDataType::Float,
+        gl,
+    )
+}
@@ -0,0 +1,78 @@
+//! Axis-aligned bounds and bounding sphere of a set of positions, to place the camera around a model.
+
+use super::math;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+impl Bounds {
+    /// Bounds of `positions`, None when there are none.
+    pub fn new<I: IntoIterator<Item = [f32; 3]>>(positions: I) -> Option<Bounds> {
+        let mut positions = positions.into_iter();
+        let first = positions.next()?;
+        let mut bounds = Bounds {
+            min: first,
+            max: first,
+        };
+        for p in positions {
+            for k in 0..3 {
+                bounds.min[k] = bounds.min[k].min(p[k]);
+                bounds.max[k] = bounds.max[k].max(p[k]);
+            }
+        }
+        Some(bounds)
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        math::sub(self.max, self.min)
+    }
+
+    pub fn diagonal(&self) -> f32 {
+        math::length(self.size())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+impl BoundingSphere {
+    /// A sphere containing all of `positions`, None when there are none.
+    /// Ritter's algorithm: a sphere on two distant positions, grown to include the ones left outside.
+    /// It is not the smallest sphere but is at most a few percent bigger.
+    pub fn new(positions: &[[f32; 3]]) -> Option<BoundingSphere> {
+        let first = *positions.first()?;
+        let farthest = |from: [f32; 3]| {
+            positions.iter().copied().fold(from, |far, p| {
+                if math::length(math::sub(p, from)) > math::length(math::sub(far, from)) {
+                    p
+                } else {
+                    far
+                }
+            })
+        };
+        let a = farthest(first);
+        let b = farthest(a);
+        let mut sphere = BoundingSphere {
+            center: math::scale(math::add(a, b), 0.5),
+            radius: math::length(math::sub(b, a)) * 0.5,
+        };
+        for p in positions.iter() {
+            let distance = math::length(math::sub(*p, sphere.center));
+            if distance > sphere.radius {
+                // move the center towards p just enough to reach it, keeping the opposite side in place
+                let radius = (sphere.radius + distance) * 0.5;
+                let shift = (radius - sphere.radius) / distance;
+                sphere.center = math::add(
+                    sphere.center,
+                    math::scale(math::sub(*p, sphere.center), shift),
+                );
+                sphere.radius = radius;
+            }
+        }
+        Some(sphere)
+    }
+}
@@ -0,0 +1,813 @@
+//! Binary cache of loaded models, so that opening a model again doesn't parse its .obj file.
+//! The cache of `model.obj` is written next to it as `model.obj.rmesh` and holds the obj struct as it was built:
+//! the vertices ready for a VertexBuffer, the indices, the mesh they come from, the submeshes and the materials.
+//!
+//! All numbers are little endian. The header is:
+//!
+//! | offset | content                                                                     |
+//! |--------|-----------------------------------------------------------------------------|
+//! | 0      | `RATIOMSH`                                                                  |
+//! | 8      | version (u32)                                                               |
+//! | 12     | layout flags (u32): texture coordinates, normals, tangents, indexed, colors |
+//! | 16     | floats per vertex (u32)                                                     |
+//! | 20     | vertex count (u64)                                                          |
+//! | 28     | index count (u64)                                                           |
+//! | 36     | bounds of the positions: min and max (6 f32)                                |
+//! | 60     | size of the source file (u64)                                               |
+//! | 68     | modification time of the source file: seconds (u64), nanoseconds (u32)      |
+//! | 80     | FNV-1a hash of the source file (u64)                                        |
+//! | 88     | crease angle in radians, NaN for none (f32), tangent mode (u32)             |
+//!
+//! The cache is stale when it was written by another version or with other attributes, or when the size of the
+//! source changed. When only the modification time changed the source is hashed again: if the content is the same
+//! the cache is still used and its modification time updated. Changes to the .mtl files are not tracked.
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::mesh::{Corner, Mesh};
+use super::mtl::{Material, TextureMap};
+use super::normals::NormalMode;
+use super::tangents::TangentMode;
+use super::{obj, Primitive, Submesh};
+
+const MAGIC: &[u8; 8] = b"RATIOMSH";
+const VERSION: u32 = 3;
+const MODIFIED_OFFSET: u64 = 68;
+
+const TEXTURE_COORDINATES: u32 = 1;
+const NORMALS: u32 = 2;
+const TANGENTS: u32 = 4;
+const INDEXED: u32 = 8;
+const COLORS: u32 = 16;
+
+/// Stands for None in optional indices.
+const NONE: u32 = std::u32::MAX;
+
+/// Where the cache of a source file is written.
+pub fn cache_path(source: &str) -> PathBuf {
+    PathBuf::from(format!("{}.rmesh", source))
+}
+
+/// FNV-1a, fast enough to go through a big model at the speed it is read from disk.
+pub struct Hasher(u64);
+impl Hasher {
+    pub fn new() -> Hasher {
+        Hasher(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn hash(data: &[u8]) -> u64 {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+fn hash_file(path: &str) -> io::Result<u64> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 1 << 20];
+    let mut hasher = Hasher::new();
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// Size and modification time (seconds and nanoseconds since the epoch, 0 if unknown) of a file.
+fn stamp(path: &str) -> io::Result<(u64, u64, u32)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or((0, 0), |time| (time.as_secs(), time.subsec_nanos()));
+    Ok((metadata.len(), modified.0, modified.1))
+}
+
+fn layout_flags(
+    use_texture_coordinates: bool,
+    use_normals: bool,
+    use_tangents: bool,
+    use_colors: bool,
+) -> u32 {
+    let mut flags = 0;
+    if use_texture_coordinates {
+        flags |= TEXTURE_COORDINATES;
+    }
+    if use_normals {
+        flags |= NORMALS;
+    }
+    if use_tangents {
+        flags |= TANGENTS;
+    }
+    if use_colors {
+        flags |= COLORS;
+    }
+    flags
+}
+
+/// Write the cache of `source`, whose content hashes to `source_hash`.
+/// The file is written under another name and then renamed, so that a cache is never left half written.
+pub fn write(obj: &obj, source: &str, source_hash: u64) -> io::Result<()> {
+    let (size, seconds, nanoseconds) = stamp(source)?;
+    let mut out = Writer(Vec::with_capacity(
+        4 * obj.vertices.len() + 4 * obj.indices.as_ref().map_or(0, |i| i.len()) + 1024,
+    ));
+
+    // header
+    let mut flags = layout_flags(
+        obj.use_texture_coordinates,
+        obj.use_normals,
+        obj.use_tangents,
+        obj.use_colors,
+    );
+    if obj.indices.is_some() {
+        flags |= INDEXED;
+    }
+    out.0.extend_from_slice(MAGIC);
+    out.u32(VERSION);
+    out.u32(flags);
+    out.u32(obj.stride as u32);
+    out.u64(obj.get_vertices_count() as u64);
+    out.u64(obj.indices.as_ref().map_or(0, |i| i.len()) as u64);
+    let (min, max) = bounds(&obj.mesh.positions);
+    out.floats(&min);
+    out.floats(&max);
+    out.u64(size);
+    out.u64(seconds);
+    out.u32(nanoseconds);
+    out.u64(source_hash);
+    out.f32(obj.crease_angle.unwrap_or(std::f32::NAN));
+    out.u32(match obj.tangent_mode {
+        TangentMode::Smooth => 0,
+        TangentMode::MikkTSpace => 1,
+    });
+
+    // data
+    out.floats(&obj.vertices);
+    if let Some(indices) = &obj.indices {
+        for &index in indices {
+            out.u32(index);
+        }
+    }
+    write_mesh(&mut out, &obj.mesh);
+    out.u64(obj.submeshes.len() as u64);
+    for submesh in obj.submeshes.iter() {
+        out.optional_string(&submesh.object);
+        out.optional_string(&submesh.group);
+        out.u32(submesh.material.map_or(NONE, |m| m as u32));
+        out.u8((submesh.primitive == Primitive::Points) as u8);
+        out.u64(submesh.start as u64);
+        out.u64(submesh.count as u64);
+        out.u8(submesh.visible as u8);
+    }
+    out.u64(obj.materials.len() as u64);
+    for material in obj.materials.iter() {
+        write_material(&mut out, material);
+    }
+
+    let path = cache_path(source);
+    let temporary = PathBuf::from(format!("{}.tmp", path.to_string_lossy()));
+    std::fs::write(&temporary, &out.0)?;
+    std::fs::rename(&temporary, &path)
+}
+
+/// Read the cache of `source`, None if there is no cache or if it is stale.
+/// Errors are returned only for caches that can't be read or are corrupted.
+pub fn read(
+    source: &str,
+    use_texture_coordinates: bool,
+    use_normals: bool,
+    use_tangents: bool,
+    use_colors: bool,
+) -> io::Result<Option<obj>> {
+    let path = cache_path(source);
+    let data = match std::fs::read(&path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let mut input = Reader { data: &data, at: 0 };
+
+    // header
+    if input.bytes(8)? != MAGIC || input.u32()? != VERSION {
+        return Ok(None);
+    }
+    let flags = input.u32()?;
+    if flags & !INDEXED
+        != layout_flags(
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )
+    {
+        return Ok(None);
+    }
+    let stride = input.u32()? as usize;
+    if stride
+        != obj::stride_of(
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )
+    {
+        return Err(corrupted());
+    }
+    let vertex_count = input.u64()? as usize;
+    let index_count = input.u64()? as usize;
+    input.floats(6)?; // bounds
+    let size = input.u64()?;
+    let modified = (input.u64()?, input.u32()?);
+    let source_hash = input.u64()?;
+
+    let (source_size, seconds, nanoseconds) = stamp(source)?;
+    if size != source_size {
+        return Ok(None);
+    }
+    if modified != (seconds, nanoseconds) {
+        if hash_file(source)? != source_hash {
+            return Ok(None);
+        }
+        refresh_modified(&path, seconds, nanoseconds)?;
+    }
+
+    let crease_angle = input.f32()?;
+    let tangent_mode = match input.u32()? {
+        0 => TangentMode::Smooth,
+        _ => TangentMode::MikkTSpace,
+    };
+
+    // data
+    let vertices = input.floats(vertex_count.saturating_mul(stride))?;
+    let indices: Option<Vec<u32>> = if flags & INDEXED != 0 {
+        let bytes = input.bytes(index_count.saturating_mul(4))?;
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+        )
+    } else {
+        None
+    };
+    let mesh = read_mesh(&mut input)?;
+    let mut submeshes = Vec::new();
+    for _ in 0..input.count()? {
+        submeshes.push(Submesh {
+            object: input.optional_string()?,
+            group: input.optional_string()?,
+            material: input.optional_index()?,
+            primitive: if input.u8()? != 0 {
+                Primitive::Points
+            } else {
+                Primitive::Triangles
+            },
+            start: input.u64()? as usize,
+            count: input.u64()? as usize,
+            visible: input.u8()? != 0,
+        });
+    }
+    let mut materials = Vec::new();
+    for _ in 0..input.count()? {
+        materials.push(read_material(&mut input)?);
+    }
+
+    // a cache that reads well can still be wrong: what would be used as an index must be in range
+    let corners = mesh.corners.len();
+    let vertices_in_mesh = corners + mesh.points.len();
+    // one vertex for each corner and point, or one index after build_indices
+    let vertices_match = match &indices {
+        Some(indices) => {
+            indices.len() == vertices_in_mesh
+                && indices.iter().all(|&i| (i as usize) < vertex_count)
+        }
+        None => vertex_count == vertices_in_mesh,
+    };
+    let submeshes_in_range = submeshes.iter().all(|submesh| {
+        let end = match submesh.start.checked_add(submesh.count) {
+            Some(end) => end,
+            None => return false,
+        };
+        let in_place = match submesh.primitive {
+            Primitive::Triangles => end <= corners,
+            Primitive::Points => submesh.start >= corners && end <= vertices_in_mesh,
+        };
+        in_place && submesh.material.map_or(true, |m| m < materials.len())
+    });
+    if !vertices_match || !submeshes_in_range {
+        return Err(corrupted());
+    }
+
+    Ok(Some(obj {
+        mesh,
+        vertices,
+        indices,
+        stride,
+        use_texture_coordinates,
+        use_normals,
+        use_tangents,
+        use_colors,
+        crease_angle: if crease_angle.is_nan() {
+            None
+        } else {
+            Some(crease_angle)
+        },
+        normal_mode: NormalMode::File,
+        tangent_mode,
+        materials,
+        submeshes,
+        cage: None,
+    }))
+}
+
+/// Store the new modification time of a source whose content didn't change, so that it is not hashed every time.
+fn refresh_modified(path: &Path, seconds: u64, nanoseconds: u32) -> io::Result<()> {
+    let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+    file.seek(SeekFrom::Start(MODIFIED_OFFSET))?;
+    file.write_all(&seconds.to_le_bytes())?;
+    file.write_all(&nanoseconds.to_le_bytes())
+}
+
+/// Smallest and biggest coordinates of the positions, zero when there are none.
+fn bounds(positions: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    if positions.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    let mut min = [std::f32::INFINITY; 3];
+    let mut max = [std::f32::NEG_INFINITY; 3];
+    for position in positions {
+        for i in 0..3 {
+            min[i] = min[i].min(position[i]);
+            max[i] = max[i].max(position[i]);
+        }
+    }
+    (min, max)
+}
+
+fn write_mesh(out: &mut Writer, mesh: &Mesh) {
+    out.u64(mesh.positions.len() as u64);
+    for position in mesh.positions.iter() {
+        out.floats(position);
+    }
+    out.u64(mesh.uvs.len() as u64);
+    for uv in mesh.uvs.iter() {
+        out.floats(uv);
+    }
+    out.u64(mesh.normals.len() as u64);
+    for normal in mesh.normals.iter() {
+        out.floats(normal);
+    }
+    out.u64(mesh.colors.len() as u64);
+    for color in mesh.colors.iter() {
+        out.floats(color);
+    }
+    write_corners(out, &mesh.corners);
+    out.u64(mesh.smoothing_groups.len() as u64);
+    for &group in mesh.smoothing_groups.iter() {
+        out.u32(group);
+    }
+    out.u64(mesh.polygons.len() as u64);
+    for &count in mesh.polygons.iter() {
+        out.u32(count);
+    }
+    write_corners(out, &mesh.points);
+}
+
+fn write_corners(out: &mut Writer, corners: &[Corner]) {
+    out.u64(corners.len() as u64);
+    for corner in corners.iter() {
+        out.u32(corner.position);
+        out.u32(corner.uv.unwrap_or(NONE));
+        out.u32(corner.normal.unwrap_or(NONE));
+    }
+}
+
+fn read_mesh(input: &mut Reader) -> io::Result<Mesh> {
+    let mut mesh = Mesh::default();
+    let count = input.count()?;
+    mesh.positions = input
+        .floats(3 * count)?
+        .chunks_exact(3)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    let count = input.count()?;
+    mesh.uvs = input
+        .floats(2 * count)?
+        .chunks_exact(2)
+        .map(|t| [t[0], t[1]])
+        .collect();
+    let count = input.count()?;
+    mesh.normals = input
+        .floats(3 * count)?
+        .chunks_exact(3)
+        .map(|n| [n[0], n[1], n[2]])
+        .collect();
+    let count = input.count()?;
+    mesh.colors = input
+        .floats(3 * count)?
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect();
+    if !mesh.colors.is_empty() && mesh.colors.len() != mesh.positions.len() {
+        return Err(corrupted());
+    }
+    mesh.corners = read_corners(input, &mesh)?;
+    if mesh.corners.len() % 3 != 0 {
+        return Err(corrupted());
+    }
+    let count = input.count()?;
+    mesh.smoothing_groups.reserve(count);
+    for _ in 0..count {
+        mesh.smoothing_groups.push(input.u32()?);
+    }
+    if mesh.smoothing_groups.len() != mesh.triangles_count() {
+        return Err(corrupted());
+    }
+    let count = input.count()?;
+    mesh.polygons.reserve(count);
+    for _ in 0..count {
+        mesh.polygons.push(input.u32()?);
+    }
+    if !mesh.polygons.is_empty()
+        && mesh.polygons.iter().map(|&n| n as usize).sum::<usize>() != mesh.triangles_count()
+    {
+        return Err(corrupted());
+    }
+    mesh.points = read_corners(input, &mesh)?;
+    Ok(mesh)
+}
+
+/// Corners whose indices are checked against the attributes of the mesh.
+fn read_corners(input: &mut Reader, mesh: &Mesh) -> io::Result<Vec<Corner>> {
+    let count = input.count()?;
+    let mut corners = Vec::with_capacity(count);
+    for _ in 0..count {
+        let corner = Corner {
+            position: input.u32()?,
+            uv: input.optional_index()?.map(|uv| uv as u32),
+            normal: input.optional_index()?.map(|normal| normal as u32),
+        };
+        if corner.position as usize >= mesh.positions.len()
+            || corner.uv.map_or(false, |uv| uv as usize >= mesh.uvs.len())
+            || corner
+                .normal
+                .map_or(false, |n| n as usize >= mesh.normals.len())
+        {
+            return Err(corrupted());
+        }
+        corners.push(corner);
+    }
+    Ok(corners)
+}
+
+fn write_material(out: &mut Writer, material: &Material) {
+    out.string(&material.name);
+    out.floats(&material.diffuse);
+    out.floats(&material.specular);
+    out.f32(material.shininess);
+    out.f32(material.dissolve);
+    out.u32(material.illum);
+    for map in [
+        &material.diffuse_map,
+        &material.normal_map,
+        &material.specular_map,
+        &material.alpha_map,
+    ]
+    .iter()
+    {
+        match map {
+            Some(map) => {
+                out.u8(1);
+                out.string(&map.path.to_string_lossy());
+                out.f32(map.bump_multiplier);
+                out.floats(&map.offset);
+                out.floats(&map.scale);
+                out.u8(map.clamp as u8);
+            }
+            None => out.u8(0),
+        }
+    }
+}
+
+fn read_material(input: &mut Reader) -> io::Result<Material> {
+    let mut material = Material::new(&input.string()?);
+    material.diffuse = input.vec3()?;
+    material.specular = input.vec3()?;
+    material.shininess = input.f32()?;
+    material.dissolve = input.f32()?;
+    material.illum = input.u32()?;
+    let mut maps = Vec::with_capacity(4);
+    for _ in 0..4 {
+        maps.push(if input.u8()? != 0 {
+            Some(TextureMap {
+                path: PathBuf::from(input.string()?),
+                data: None,
+                bump_multiplier: input.f32()?,
+                offset: input.vec3()?,
+                scale: input.vec3()?,
+                clamp: input.u8()? != 0,
+            })
+        } else {
+            None
+        });
+    }
+    material.alpha_map = maps.pop().unwrap();
+    material.specular_map = maps.pop().unwrap();
+    material.normal_map = maps.pop().unwrap();
+    material.diffuse_map = maps.pop().unwrap();
+    Ok(material)
+}
+
+fn corrupted() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupted mesh cache")
+}
+
+struct Writer(Vec<u8>);
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for &value in values {
+            self.f32(value);
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.u64(value.len() as u64);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn optional_string(&mut self, value: &Option<String>) {
+        match value {
+            Some(value) => {
+                self.u8(1);
+                self.string(value);
+            }
+            None => self.u8(0),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if count > self.data.len() - self.at {
+            return Err(corrupted());
+        }
+        let bytes = &self.data[self.at..self.at + count];
+        self.at += count;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn floats(&mut self, count: usize) -> io::Result<Vec<f32>> {
+        let bytes = self.bytes(count.saturating_mul(4))?;
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect())
+    }
+
+    fn vec3(&mut self) -> io::Result<[f32; 3]> {
+        Ok([self.f32()?, self.f32()?, self.f32()?])
+    }
+
+    /// A number of elements, which can't be more than the bytes left.
+    fn count(&mut self) -> io::Result<usize> {
+        let count = self.u64()?;
+        if count > (self.data.len() - self.at) as u64 {
+            return Err(corrupted());
+        }
+        Ok(count as usize)
+    }
+
+    fn optional_index(&mut self) -> io::Result<Option<usize>> {
+        let index = self.u32()?;
+        Ok(if index == NONE {
+            None
+        } else {
+            Some(index as usize)
+        })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.count()?;
+        String::from_utf8(self.bytes(length)?.to_vec()).map_err(|_| corrupted())
+    }
+
+    fn optional_string(&mut self) -> io::Result<Option<String>> {
+        Ok(if self.u8()? != 0 {
+            Some(self.string()?)
+        } else {
+            None
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::super::obj;
+    use super::{cache_path, hash, read, write, MODIFIED_OFFSET, VERSION};
+
+    const MODEL: &str = "mtllib model.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+o square
+usemtl red
+f 1/1/1 2/2/1 3/2/1 4/1/1
+p 2
+";
+
+    const LIBRARY: &str = "newmtl red
+Kd 1 0 0
+";
+
+    /// A model and its cache in a folder of their own, removed when dropped.
+    struct Cached {
+        folder: PathBuf,
+        source: String,
+    }
+    impl Cached {
+        fn new(name: &str) -> Cached {
+            let folder =
+                std::env::temp_dir().join(format!("ratio_cache_{}_{}", name, std::process::id()));
+            std::fs::create_dir_all(&folder).unwrap();
+            std::fs::write(folder.join("model.mtl"), LIBRARY).unwrap();
+            let source = folder.join("model.obj").to_string_lossy().into_owned();
+            std::fs::write(&source, MODEL).unwrap();
+            let model = load(&source);
+            write(&model, &source, hash(MODEL.as_bytes())).unwrap();
+            Cached { folder, source }
+        }
+
+        fn read(&self) -> std::io::Result<Option<obj>> {
+            read(&self.source, true, true, true, false)
+        }
+
+        fn cache(&self) -> Vec<u8> {
+            std::fs::read(cache_path(&self.source)).unwrap()
+        }
+
+        fn set_cache(&self, data: &[u8]) {
+            std::fs::write(cache_path(&self.source), data).unwrap();
+        }
+
+        /// Make the cache look written for another modification time of the source, so that the source is hashed.
+        fn forget_modified(&self) {
+            let mut data = self.cache();
+            let at = MODIFIED_OFFSET as usize;
+            data[at..at + 12].copy_from_slice(&[0; 12]);
+            self.set_cache(&data);
+        }
+    }
+    impl Drop for Cached {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.folder);
+        }
+    }
+
+    fn load(source: &str) -> obj {
+        let mut model = obj::load_new(source, true, true, true, false).unwrap();
+        model.build_indices();
+        model
+    }
+
+    #[test]
+    fn read_back() {
+        let cached = Cached::new("read_back");
+        let model = load(&cached.source);
+        let read = cached.read().unwrap().expect("the cache is valid");
+        assert_eq!(read.vertices, model.vertices);
+        assert_eq!(read.indices, model.indices);
+        assert_eq!(read.stride, model.stride);
+        assert_eq!(read.mesh.positions, model.mesh.positions);
+        assert_eq!(read.mesh.corners, model.mesh.corners);
+        assert_eq!(read.mesh.points, model.mesh.points);
+        assert_eq!(
+            format!("{:?}", read.submeshes),
+            format!("{:?}", model.submeshes)
+        );
+        assert_eq!(read.materials.len(), 1);
+        assert_eq!(read.materials[0].name, "red");
+        assert_eq!(read.materials[0].diffuse, [1.0, 0.0, 0.0]);
+
+        // other attributes need another cache
+        assert!(read_with_colors(&cached.source).is_none());
+    }
+
+    fn read_with_colors(source: &str) -> Option<obj> {
+        read(source, true, true, true, true).unwrap()
+    }
+
+    #[test]
+    fn other_version() {
+        let cached = Cached::new("other_version");
+        let mut data = cached.cache();
+        data[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        cached.set_cache(&data);
+        assert!(cached.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn source_changed() {
+        let cached = Cached::new("source_changed");
+        // same size, other content
+        std::fs::write(&cached.source, MODEL.replace("v 1 1 0", "v 2 2 0")).unwrap();
+        cached.forget_modified();
+        assert!(cached.read().unwrap().is_none());
+
+        // another size is enough, without hashing
+        std::fs::write(&cached.source, format!("{}\n", MODEL)).unwrap();
+        assert!(cached.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn source_touched() {
+        let cached = Cached::new("source_touched");
+        cached.forget_modified();
+        // the content is the same: the cache is used and its modification time updated
+        assert!(cached.read().unwrap().is_some());
+        let at = MODIFIED_OFFSET as usize;
+        assert_ne!(&cached.cache()[at..at + 12], &[0; 12]);
+    }
+
+    #[test]
+    fn truncated() {
+        let cached = Cached::new("truncated");
+        let data = cached.cache();
+        for length in (0..data.len()).step_by(3) {
+            cached.set_cache(&data[..length]);
+            assert!(cached.read().is_err(), "{} bytes of {}", length, data.len());
+        }
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let cached = Cached::new("index_out_of_range");
+        let model = load(&cached.source);
+        let mut data = cached.cache();
+        // the indices follow the 96 bytes of the header and the vertices
+        let at = 96 + 4 * model.vertices.len();
+        assert_eq!(&data[at..at + 4], &model.get_indices()[0].to_le_bytes());
+        data[at..at + 4].copy_from_slice(&1000u32.to_le_bytes());
+        cached.set_cache(&data);
+        assert!(cached.read().is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+
+use super::parser::parse_f32;
+
+/// The reason why an .obj file could not be loaded.
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    /// The file could not be opened or read.
+    Io(std::io::Error),
+    /// A value that should be a number is not a valid f32.
+    BadFloat(String),
+    /// A face index is not a valid integer.
+    BadIndex(String),
+    /// A face references an element that does not exist (1-based index as written in the file).
+    IndexOutOfRange {
+        element: &'static str,
+        index: i64,
+        count: usize,
+    },
+    /// The face has a number of vertices the loader cannot handle.
+    UnsupportedFaceArity(usize),
+    /// A value is not accepted for some other reason, described by the message.
+    BadValue(String),
+    /// A record has less values than required.
+    MissingValues {
+        record: &'static str,
+        expected: usize,
+        found: usize,
+    },
+}
+
+/// A line of a text file being parsed, used to build errors that point into the file.
+/// It is kept as bytes: keywords and numbers are ASCII and names are converted only when needed,
+/// so that the text never needs to be validated as a whole.
+pub struct Line<'a> {
+    pub path: &'a str,
+    /// 1-based
+    pub number: usize,
+    pub text: &'a [u8],
+}
+impl Line<'_> {
+    /// 1-based column of a word taken from this line.
+    pub fn column_of(&self, word: &[u8]) -> usize {
+        word.as_ptr() as usize - self.text.as_ptr() as usize + 1
+    }
+
+    pub fn error(&self, word: &[u8], kind: ObjErrorKind) -> ObjError {
+        ObjError::new(self.path, self.number, self.column_of(word), kind)
+    }
+
+    /// A statement with less values than it needs, pointing after the last one.
+    pub fn missing_values(&self, record: &'static str, expected: usize, found: usize) -> ObjError {
+        let column = String::from_utf8_lossy(self.text).trim_end().len() + 1;
+        ObjError::new(
+            self.path,
+            self.number,
+            column,
+            ObjErrorKind::MissingValues {
+                record,
+                expected,
+                found,
+            },
+        )
+    }
+
+    pub fn parse_float(&self, word: &[u8]) -> Result<f32, ObjError> {
+        parse_f32(word).ok_or_else(|| {
+            let value = String::from_utf8_lossy(word).into_owned();
+            self.error(word, ObjErrorKind::BadFloat(value))
+        })
+    }
+
+    /// Parse the next words as floats, as many as the length of `values`.
+    pub fn parse_floats<'w>(
+        &self,
+        words: &mut impl Iterator<Item = &'w [u8]>,
+        record: &'static str,
+        values: &mut [f32],
+    ) -> Result<(), ObjError> {
+        let count = values.len();
+        for (found, value) in values.iter_mut().enumerate() {
+            match words.next() {
+                Some(word) => *value = self.parse_float(word)?,
+                None => return Err(self.missing_values(record, count, found)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by obj::load_new, pointing to the place in the file where loading failed.
+/// Line and column are 1-based, a column of 0 means the error is not related to a specific value.
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+    pub kind: ObjErrorKind,
+}
+impl ObjError {
+    pub fn new(path: &str, line: usize, column: usize, kind: ObjErrorKind) -> ObjError {
+        ObjError {
+            path: String::from(path),
+            line,
+            column,
+            kind,
+        }
+    }
+
+    pub fn io(path: &str, line: usize, err: std::io::Error) -> ObjError {
+        ObjError::new(path, line, 0, ObjErrorKind::Io(err))
+    }
+}
+
+impl fmt::Display for ObjErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjErrorKind::Io(err) => write!(f, "I/O error: {}", err),
+            ObjErrorKind::BadFloat(value) => write!(f, "'{}' is not a valid number", value),
+            ObjErrorKind::BadIndex(value) => write!(f, "'{}' is not a valid index", value),
+            ObjErrorKind::IndexOutOfRange {
+                element,
+                index,
+                count,
+            } => write!(
+                f,
+                "{} index {} is out of range, the file defines {} of them so far",
+                element, index, count
+            ),
+            ObjErrorKind::BadValue(message) => write!(f, "{}", message),
+            ObjErrorKind::UnsupportedFaceArity(n) => {
+                write!(f, "faces with {} vertices are not supported", n)
+            }
+            ObjErrorKind::MissingValues {
+                record,
+                expected,
+                found,
+            } => write!(
+                f,
+                "'{}' needs {} values but only {} were found",
+                record, expected, found
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path, self.kind)
+        } else if self.column == 0 {
+            write!(f, "{}:{}: {}", self.path, self.line, self.kind)
+        } else {
+            write!(
+                f,
+                "{}:{}:{}: {}",
+                self.path, self.line, self.column, self.kind
+            )
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ObjErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
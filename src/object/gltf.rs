@@ -0,0 +1,773 @@
+//! Loader for glTF 2.0 models, either .gltf (JSON with external or embedded buffers) or .glb (binary container).
+//! https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+//! The meshes of the nodes of the scene are transformed to world space and joined in a single Mesh, with a submesh
+//! for each primitive named after its node and its mesh. Texture coordinates are flipped to the convention of .obj
+//! files, with the origin in the bottom left corner.
+//! Primitives made of points become submeshes of points after those of triangles, lines are skipped.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use super::error::{ObjError, ObjErrorKind};
+use super::math::{self, Matrix};
+use super::mesh::{Corner, Mesh};
+use super::mtl::{Material, TextureMap};
+use super::parser::ObjData;
+use super::resolver::{self, Resolver};
+use super::{Primitive, Submesh};
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+const POINTS: u64 = 0;
+const TRIANGLES: u64 = 4;
+const TRIANGLE_STRIP: u64 = 5;
+const TRIANGLE_FAN: u64 = 6;
+
+const CLAMP_TO_EDGE: u64 = 33071;
+
+/// Load a .gltf or .glb file whose content is `data`.
+/// `path` is used for errors, the buffers and images stored in other files are read through `resolver`.
+pub fn load(data: &[u8], path: &str, resolver: &dyn Resolver) -> Result<ObjData, ObjError> {
+    let (json, bin) = if data.len() >= 4 && read_u32(data, 0) == GLB_MAGIC {
+        split_glb(data, path)?
+    } else {
+        (data, None)
+    };
+    let document: Value = serde_json::from_slice(json).map_err(|err| {
+        ObjError::new(
+            path,
+            err.line(),
+            err.column(),
+            ObjErrorKind::BadValue(format!("invalid JSON: {}", err)),
+        )
+    })?;
+
+    let mut gltf = Gltf {
+        path,
+        resolver,
+        document: &document,
+        buffers: Vec::new(),
+        images: Vec::new(),
+    };
+    gltf.load_buffers(bin)?;
+
+    let mut data = ObjData {
+        mesh: Mesh::default(),
+        materials: Vec::new(),
+        submeshes: Vec::new(),
+    };
+    for (i, material) in gltf.array("materials").iter().enumerate() {
+        let material = gltf.material(i, material)?;
+        data.materials.push(material);
+    }
+    for node in gltf.scene_roots()? {
+        gltf.add_node(node, &math::IDENTITY, 0, &mut data)?;
+    }
+    // points are drawn after all the triangles
+    let (triangles, mut points): (Vec<Submesh>, Vec<Submesh>) = data
+        .submeshes
+        .into_iter()
+        .partition(|submesh| submesh.primitive == Primitive::Triangles);
+    for submesh in points.iter_mut() {
+        submesh.start += data.mesh.corners.len();
+    }
+    data.submeshes = triangles;
+    data.submeshes.append(&mut points);
+    Ok(data)
+}
+
+/// The JSON and binary chunks of a .glb file.
+fn split_glb<'a>(data: &'a [u8], path: &str) -> Result<(&'a [u8], Option<&'a [u8]>), ObjError> {
+    let error = |message: &str| {
+        ObjError::new(
+            path,
+            0,
+            0,
+            ObjErrorKind::BadValue(format!("invalid .glb file: {}", message)),
+        )
+    };
+    if data.len() < 12 {
+        return Err(error("the header is truncated"));
+    }
+    if read_u32(data, 4) != 2 {
+        return Err(error("only version 2 is supported"));
+    }
+    let length = (read_u32(data, 8) as usize).min(data.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = read_u32(data, offset) as usize;
+        let chunk_type = read_u32(data, offset + 4);
+        let start = offset + 8;
+        if chunk_length > length - start {
+            return Err(error("a chunk is truncated"));
+        }
+        let chunk = &data[start..start + chunk_length];
+        match chunk_type {
+            CHUNK_JSON if json.is_none() => json = Some(chunk),
+            CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            _ => {}
+        }
+        // chunks are aligned to 4 bytes
+        offset = start + (chunk_length + 3) / 4 * 4;
+    }
+    match json {
+        Some(json) => Ok((json, bin)),
+        None => Err(error("the JSON chunk is missing")),
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+fn usize_of(value: &Value, key: &str) -> Option<usize> {
+    value.get(key)?.as_u64().map(|n| n as usize)
+}
+
+fn f32_of(value: &Value, key: &str) -> Option<f32> {
+    value.get(key)?.as_f64().map(|n| n as f32)
+}
+
+fn floats_of(value: &Value, key: &str, count: usize) -> Option<Vec<f32>> {
+    let values: Vec<f32> = value
+        .get(key)?
+        .as_array()?
+        .iter()
+        .filter_map(|n| n.as_f64().map(|n| n as f32))
+        .collect();
+    if values.len() == count {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+fn name_of(value: &Value) -> Option<String> {
+    value
+        .get("name")
+        .and_then(Value::as_str)
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+}
+
+struct Gltf<'a> {
+    path: &'a str,
+    resolver: &'a dyn Resolver,
+    document: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    /// encoded images embedded in the file, decoded once even when used by many materials
+    images: Vec<Option<Arc<Vec<u8>>>>,
+}
+impl<'a> Gltf<'a> {
+    fn error(&self, message: String) -> ObjError {
+        ObjError::new(self.path, 0, 0, ObjErrorKind::BadValue(message))
+    }
+
+    /// A top level array of the document, empty when missing.
+    fn array(&self, name: &str) -> &'a [Value] {
+        self.document
+            .get(name)
+            .and_then(Value::as_array)
+            .map_or(&[], |array| array.as_slice())
+    }
+
+    /// An element of a top level array, with an error when it doesn't exist.
+    fn item(&self, collection: &str, index: usize) -> Result<&'a Value, ObjError> {
+        self.array(collection).get(index).ok_or_else(|| {
+            self.error(format!(
+                "{} {} is referenced but does not exist",
+                collection, index
+            ))
+        })
+    }
+
+    /// A required index property, like the `buffer` of a bufferView.
+    fn index(&self, value: &Value, key: &str, owner: &str) -> Result<usize, ObjError> {
+        usize_of(value, key).ok_or_else(|| self.error(format!("{} without '{}'", owner, key)))
+    }
+
+    fn load_buffers(&mut self, bin: Option<&[u8]>) -> Result<(), ObjError> {
+        for (i, buffer) in self.array("buffers").iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Value::as_str) {
+                Some(uri) if uri.starts_with("data:") => self.decode_data_uri(uri)?,
+                Some(uri) => {
+                    let name = percent_decode(uri);
+                    self.resolver
+                        .read(&name)
+                        .map_err(|err| ObjError::io(&self.resolver.describe(&name), 0, err))?
+                }
+                // only the first buffer of a .glb file can live in its binary chunk
+                None if i == 0 && bin.is_some() => bin.unwrap().to_vec(),
+                None => return Err(self.error(format!("buffer {} has no data", i))),
+            };
+            let length = usize_of(buffer, "byteLength").unwrap_or(0);
+            if data.len() < length {
+                return Err(self.error(format!(
+                    "buffer {} has {} bytes instead of {}",
+                    i,
+                    data.len(),
+                    length
+                )));
+            }
+            self.buffers.push(data);
+        }
+        self.images = vec![None; self.array("images").len()];
+        Ok(())
+    }
+
+    /// Content of a `data:[<mime type>][;base64],<data>` URI.
+    fn decode_data_uri(&self, uri: &str) -> Result<Vec<u8>, ObjError> {
+        let comma = uri
+            .find(',')
+            .ok_or_else(|| self.error(String::from("invalid data URI")))?;
+        let (header, content) = (&uri[5..comma], &uri[comma + 1..]);
+        if header.ends_with(";base64") {
+            base64_decode(content)
+                .ok_or_else(|| self.error(String::from("invalid base64 data in a data URI")))
+        } else {
+            Ok(percent_decode(content).into_bytes())
+        }
+    }
+
+    fn view_bytes(&self, view: usize) -> Result<&[u8], ObjError> {
+        let value = self.item("bufferViews", view)?;
+        let buffer = self.index(value, "buffer", "bufferView")?;
+        let buffer = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| self.error(format!("buffer {} does not exist", buffer)))?;
+        let offset = usize_of(value, "byteOffset").unwrap_or(0);
+        let length = self.index(value, "byteLength", "bufferView")?;
+        if offset > buffer.len() || length > buffer.len() - offset {
+            return Err(self.error(format!("bufferView {} is out of its buffer", view)));
+        }
+        Ok(&buffer[offset..offset + length])
+    }
+
+    /// Read `count` elements of `components` values each from a bufferView.
+    /// `stride` is the distance in bytes between elements, when they are not tightly packed.
+    fn read_view(
+        &self,
+        view: usize,
+        offset: usize,
+        component_type: u64,
+        components: usize,
+        count: usize,
+        stride: Option<usize>,
+    ) -> Result<Vec<f64>, ObjError> {
+        let bytes = self.view_bytes(view)?;
+        let size = match component_type {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => return Err(self.error(format!("unknown component type {}", component_type))),
+        };
+        let element = size * components;
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            return Err(self.error(format!(
+                "bufferView {} has a byteStride smaller than its elements",
+                view
+            )));
+        }
+        if count > 0 {
+            let end = (count - 1)
+                .checked_mul(stride)
+                .and_then(|n| offset.checked_add(element).and_then(|e| n.checked_add(e)));
+            if end.map_or(true, |end| end > bytes.len()) {
+                return Err(self.error(format!("bufferView {} is too short", view)));
+            }
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for i in 0..count {
+            let start = offset + i * stride;
+            for c in 0..components {
+                let b = &bytes[start + c * size..];
+                values.push(match component_type {
+                    BYTE => b[0] as i8 as f64,
+                    UNSIGNED_BYTE => b[0] as f64,
+                    SHORT => i16::from_le_bytes([b[0], b[1]]) as f64,
+                    UNSIGNED_SHORT => u16::from_le_bytes([b[0], b[1]]) as f64,
+                    UNSIGNED_INT => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                    _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+                });
+            }
+        }
+        Ok(values)
+    }
+
+    /// Values of an accessor and the number of values per element.
+    /// Normalized integers are mapped to [0, 1] or [-1, 1], sparse values replace the ones of the bufferView.
+    fn accessor(&self, index: usize) -> Result<(Vec<f64>, usize), ObjError> {
+        let accessor = self.item("accessors", index)?;
+        let component_type = accessor
+            .get("componentType")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| self.error(format!("accessor {} has no componentType", index)))?;
+        let count = self.index(accessor, "count", "accessor")?;
+        let components = match accessor.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(self.error(format!("accessor {} has an unknown type", index))),
+        };
+
+        let mut values = match usize_of(accessor, "bufferView") {
+            Some(view) => {
+                let stride = usize_of(self.item("bufferViews", view)?, "byteStride");
+                let offset = usize_of(accessor, "byteOffset").unwrap_or(0);
+                self.read_view(view, offset, component_type, components, count, stride)?
+            }
+            None => {
+                // zeros, for sparse accessors: still no more elements than the buffers could hold
+                let bytes: usize = self.buffers.iter().map(Vec::len).sum();
+                match count.checked_mul(components) {
+                    Some(values) if count <= bytes => vec![0.0; values],
+                    _ => {
+                        return Err(self.error(format!(
+                            "accessor {} has more elements than its buffers can hold",
+                            index
+                        )))
+                    }
+                }
+            }
+        };
+
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = self.index(sparse, "count", "sparse accessor")?;
+            let (indices, replacements) = match (sparse.get("indices"), sparse.get("values")) {
+                (Some(indices), Some(replacements)) => (indices, replacements),
+                _ => return Err(self.error(format!("sparse accessor {} is incomplete", index))),
+            };
+            let indices = self.read_view(
+                self.index(indices, "bufferView", "sparse accessor")?,
+                usize_of(indices, "byteOffset").unwrap_or(0),
+                indices
+                    .get("componentType")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(UNSIGNED_INT),
+                1,
+                sparse_count,
+                None,
+            )?;
+            let replacements = self.read_view(
+                self.index(replacements, "bufferView", "sparse accessor")?,
+                usize_of(replacements, "byteOffset").unwrap_or(0),
+                component_type,
+                components,
+                sparse_count,
+                None,
+            )?;
+            for (i, &target) in indices.iter().enumerate() {
+                let target = target as usize;
+                if target >= count {
+                    return Err(self.error(format!(
+                        "sparse accessor {} replaces element {} of {}",
+                        index, target, count
+                    )));
+                }
+                values[target * components..(target + 1) * components]
+                    .copy_from_slice(&replacements[i * components..(i + 1) * components]);
+            }
+        }
+
+        if accessor.get("normalized").and_then(Value::as_bool) == Some(true) {
+            let max = match component_type {
+                BYTE => 127.0,
+                UNSIGNED_BYTE => 255.0,
+                SHORT => 32767.0,
+                UNSIGNED_SHORT => 65535.0,
+                _ => 1.0,
+            };
+            for value in values.iter_mut() {
+                *value = (*value / max).max(-1.0);
+            }
+        }
+        Ok((values, components))
+    }
+
+    /// An accessor whose elements have exactly `components` values.
+    fn accessor_of(&self, index: usize, components: usize) -> Result<Vec<f64>, ObjError> {
+        let (values, found) = self.accessor(index)?;
+        if found != components {
+            return Err(self.error(format!(
+                "accessor {} has {} components instead of {}",
+                index, found, components
+            )));
+        }
+        Ok(values)
+    }
+
+    fn material(&mut self, index: usize, value: &Value) -> Result<Material, ObjError> {
+        let name = name_of(value).unwrap_or_else(|| format!("material{}", index));
+        let mut material = Material::new(&name);
+
+        // defaults of the glTF specification
+        let pbr = value.get("pbrMetallicRoughness").unwrap_or(&Value::Null);
+        let base_color = floats_of(pbr, "baseColorFactor", 4).unwrap_or_else(|| vec![1.0; 4]);
+        material.diffuse = [base_color[0], base_color[1], base_color[2]];
+        material.dissolve = base_color[3];
+        material.metallic = f32_of(pbr, "metallicFactor").unwrap_or(1.0);
+        material.roughness = f32_of(pbr, "roughnessFactor").unwrap_or(1.0);
+        material.diffuse_map = self.texture(pbr.get("baseColorTexture"))?;
+        material.metallic_roughness_map = self.texture(pbr.get("metallicRoughnessTexture"))?;
+
+        let normal = value.get("normalTexture");
+        material.normal_map = self.texture(normal)?;
+        if let (Some(map), Some(scale)) = (&mut material.normal_map, normal) {
+            map.bump_multiplier = f32_of(scale, "scale").unwrap_or(1.0);
+        }
+
+        // the renderer knows only the .mtl parameters: dielectrics reflect 4% of the light, metals their color
+        let metallic = material.metallic.max(0.0).min(1.0);
+        for i in 0..3 {
+            material.specular[i] = 0.04 + (material.diffuse[i] - 0.04) * metallic;
+        }
+        material.shininess = (1.0 - material.roughness.max(0.0).min(1.0)) * 1000.0;
+        Ok(material)
+    }
+
+    /// The texture referenced by a textureInfo, None when there isn't one or its image uses an extension.
+    fn texture(&mut self, info: Option<&Value>) -> Result<Option<TextureMap>, ObjError> {
+        let index = match info.and_then(|info| usize_of(info, "index")) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let texture = self.item("textures", index)?;
+        let source = match usize_of(texture, "source") {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let image = self.item("images", source)?;
+
+        let embedded_path = PathBuf::from(format!("{}#image{}", self.path, source));
+        let (path, data) = match image.get("uri").and_then(Value::as_str) {
+            Some(uri) if !uri.starts_with("data:") => {
+                resolver::texture(self.resolver, &percent_decode(uri))
+            }
+            uri => {
+                if self.images[source].is_none() {
+                    let data = match (uri, usize_of(image, "bufferView")) {
+                        (Some(uri), _) => self.decode_data_uri(uri)?,
+                        (None, Some(view)) => self.view_bytes(view)?.to_vec(),
+                        (None, None) => {
+                            return Err(self.error(format!("image {} has no data", source)))
+                        }
+                    };
+                    self.images[source] = Some(Arc::new(data));
+                }
+                (embedded_path, self.images[source].clone())
+            }
+        };
+
+        let clamp = usize_of(texture, "sampler")
+            .and_then(|sampler| self.array("samplers").get(sampler))
+            .and_then(|sampler| sampler.get("wrapS"))
+            .and_then(Value::as_u64)
+            == Some(CLAMP_TO_EDGE);
+        Ok(Some(TextureMap {
+            path,
+            data,
+            bump_multiplier: 1.0,
+            offset: [0.0, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0],
+            clamp,
+        }))
+    }
+
+    /// The nodes at the root of the default scene, or all the nodes without a parent if there are no scenes.
+    fn scene_roots(&self) -> Result<Vec<usize>, ObjError> {
+        let indices = |value: &Value| -> Vec<usize> {
+            value
+                .get("nodes")
+                .or_else(|| value.get("children"))
+                .and_then(Value::as_array)
+                .map_or(Vec::new(), |nodes| {
+                    nodes
+                        .iter()
+                        .filter_map(|n| n.as_u64().map(|n| n as usize))
+                        .collect()
+                })
+        };
+        if !self.array("scenes").is_empty() {
+            let scene = usize_of(self.document, "scene").unwrap_or(0);
+            return Ok(indices(self.item("scenes", scene)?));
+        }
+        let nodes = self.array("nodes");
+        let mut has_parent = vec![false; nodes.len()];
+        for node in nodes {
+            for child in indices(node) {
+                if child < has_parent.len() {
+                    has_parent[child] = true;
+                }
+            }
+        }
+        Ok((0..nodes.len()).filter(|&n| !has_parent[n]).collect())
+    }
+
+    fn add_node(
+        &self,
+        index: usize,
+        parent: &Matrix,
+        depth: usize,
+        data: &mut ObjData,
+    ) -> Result<(), ObjError> {
+        // a node can't be its own ancestor, deeper hierarchies mean there is a cycle
+        if depth > self.array("nodes").len() {
+            return Err(self.error(String::from("the node hierarchy has a cycle")));
+        }
+        let node = self.item("nodes", index)?;
+
+        let local = match floats_of(node, "matrix", 16) {
+            Some(matrix) => {
+                let mut m = [0.0; 16];
+                m.copy_from_slice(&matrix);
+                m
+            }
+            None => {
+                let t = floats_of(node, "translation", 3).unwrap_or_else(|| vec![0.0; 3]);
+                let r = floats_of(node, "rotation", 4).unwrap_or_else(|| vec![0.0, 0.0, 0.0, 1.0]);
+                let s = floats_of(node, "scale", 3).unwrap_or_else(|| vec![1.0; 3]);
+                math::from_translation_rotation_scale(
+                    [t[0], t[1], t[2]],
+                    [r[0], r[1], r[2], r[3]],
+                    [s[0], s[1], s[2]],
+                )
+            }
+        };
+        let world = math::multiply(parent, &local);
+
+        if let Some(mesh) = usize_of(node, "mesh") {
+            let mesh = self.item("meshes", mesh)?;
+            let primitives = mesh.get("primitives").and_then(Value::as_array);
+            for primitive in primitives.map_or(&[][..], |p| p.as_slice()) {
+                self.add_primitive(primitive, &world, name_of(node), name_of(mesh), data)?;
+            }
+        }
+
+        if let Some(children) = node.get("children").and_then(Value::as_array) {
+            for child in children.iter().filter_map(Value::as_u64) {
+                self.add_node(child as usize, &world, depth + 1, data)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_primitive(
+        &self,
+        primitive: &Value,
+        world: &Matrix,
+        object: Option<String>,
+        group: Option<String>,
+        data: &mut ObjData,
+    ) -> Result<(), ObjError> {
+        let mode = primitive
+            .get("mode")
+            .and_then(Value::as_u64)
+            .unwrap_or(TRIANGLES);
+        if mode != POINTS && mode != TRIANGLES && mode != TRIANGLE_STRIP && mode != TRIANGLE_FAN {
+            println!(
+                "WARN: {}: lines are not supported, a primitive was skipped",
+                self.path
+            );
+            return Ok(());
+        }
+        let attributes = primitive.get("attributes").unwrap_or(&Value::Null);
+        let positions = match usize_of(attributes, "POSITION") {
+            Some(accessor) => self.accessor_of(accessor, 3)?,
+            None => return Ok(()),
+        };
+        let count = positions.len() / 3;
+        let normals = match usize_of(attributes, "NORMAL") {
+            Some(accessor) => Some(self.accessor_of(accessor, 3)?),
+            None => None,
+        };
+        let uvs = match usize_of(attributes, "TEXCOORD_0") {
+            Some(accessor) => Some(self.accessor_of(accessor, 2)?),
+            None => None,
+        };
+        if normals.as_ref().map_or(false, |n| n.len() != 3 * count)
+            || uvs.as_ref().map_or(false, |t| t.len() != 2 * count)
+        {
+            return Err(self.error(String::from(
+                "the attributes of a primitive have different counts",
+            )));
+        }
+        let indices: Vec<usize> = match usize_of(primitive, "indices") {
+            Some(accessor) => self
+                .accessor_of(accessor, 1)?
+                .iter()
+                .map(|&i| i as usize)
+                .collect(),
+            None => (0..count).collect(),
+        };
+        if let Some(&index) = indices.iter().find(|&&i| i >= count) {
+            return Err(self.error(format!(
+                "vertex index {} is out of range, the primitive has {} vertices",
+                index, count
+            )));
+        }
+
+        let material = usize_of(primitive, "material").filter(|&m| m < data.materials.len());
+        let mesh = &mut data.mesh;
+        let bases = [mesh.positions.len(), mesh.uvs.len(), mesh.normals.len()];
+        for p in positions.chunks(3) {
+            let p = [p[0] as f32, p[1] as f32, p[2] as f32];
+            mesh.positions.push(math::transform_point(world, p));
+        }
+        if let Some(uvs) = &uvs {
+            for t in uvs.chunks(2) {
+                mesh.uvs.push([t[0] as f32, 1.0 - t[1] as f32]);
+            }
+        }
+        if let Some(normals) = &normals {
+            for n in normals.chunks(3) {
+                let n = [n[0] as f32, n[1] as f32, n[2] as f32];
+                mesh.normals.push(math::transform_normal(world, n));
+            }
+        }
+        let corner = |i: usize| Corner {
+            position: (bases[0] + i) as u32,
+            uv: uvs.as_ref().map(|_| (bases[1] + i) as u32),
+            normal: normals.as_ref().map(|_| (bases[2] + i) as u32),
+        };
+
+        let primitive = if mode == POINTS {
+            Primitive::Points
+        } else {
+            Primitive::Triangles
+        };
+        // the submeshes of points start in mesh.points until load moves them after the triangles
+        let end = match primitive {
+            Primitive::Triangles => mesh.corners.len(),
+            Primitive::Points => mesh.points.len(),
+        };
+        match data.submeshes.last() {
+            Some(submesh)
+                if submesh.primitive == primitive
+                    && submesh.material == material
+                    && submesh.object == object
+                    && submesh.group == group => {}
+            _ => data.submeshes.push(Submesh {
+                object,
+                group,
+                material,
+                primitive,
+                start: end,
+                count: 0,
+                visible: true,
+            }),
+        }
+
+        let triangles: Vec<[usize; 3]> = match mode {
+            POINTS => {
+                mesh.points.extend(indices.iter().map(|&i| corner(i)));
+                Vec::new()
+            }
+            TRIANGLE_STRIP => (2..indices.len())
+                .map(|i| {
+                    // every other triangle of a strip is wound the other way round
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            TRIANGLE_FAN => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            _ => indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+        };
+
+        // a mirroring transform turns the triangles inside out
+        let mirrored = math::determinant(world) < 0.0;
+        // without normals the specification asks for flat shading
+        let smoothing_group = if normals.is_some() { 1 } else { 0 };
+        for [a, b, c] in triangles {
+            let (b, c) = if mirrored { (c, b) } else { (b, c) };
+            mesh.push_triangle([corner(a), corner(b), corner(c)], smoothing_group);
+        }
+
+        if let Some(submesh) = data.submeshes.last_mut() {
+            let end = match primitive {
+                Primitive::Triangles => mesh.corners.len(),
+                Primitive::Points => mesh.points.len(),
+            };
+            submesh.count = end - submesh.start;
+        }
+        Ok(())
+    }
+}
+
+/// Decode standard base64, ignoring whitespace and padding.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' | b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return None,
+        };
+        accumulator = (accumulator << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+            accumulator &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// URIs escape some characters, like spaces written as `%20`.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = |b: u8| (b as char).to_digit(16);
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(high), Some(low)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push((high * 16 + low) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
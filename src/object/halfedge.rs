@@ -0,0 +1,333 @@
+//! Half-edge mesh with the polygons of a model, which the triangles of Mesh lose, for subdivision.
+//! The half-edges of each face are stored one after the other, in the winding of the face: each goes from its vertex
+//! to the vertex of the next one, and is paired to the half-edge going the other way in the next face, if any.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use super::mesh::{Corner, Mesh};
+use super::triangulate;
+use super::{Primitive, Submesh};
+
+/// No twin: the half-edge is on a border, or its edge has more than two faces.
+pub const NONE: u32 = u32::MAX;
+
+#[derive(Clone, Debug, Default)]
+pub struct HalfEdgeMesh {
+    pub positions: Vec<[f32; 3]>,
+    /// Color of each vertex, empty when the mesh has no colors.
+    pub colors: Vec<[f32; 3]>,
+    /// The half-edges of face f are `face_offsets[f]..face_offsets[f + 1]`.
+    pub face_offsets: Vec<usize>,
+    /// Smoothing group and submesh of each face.
+    pub smoothing_groups: Vec<u32>,
+    pub submeshes: Vec<u32>,
+    /// Vertex each half-edge starts from.
+    pub vertex: Vec<u32>,
+    /// Texture coordinates of the face corner at the start of each half-edge, empty when the mesh has none.
+    pub uvs: Vec<[f32; 2]>,
+    /// Face of each half-edge.
+    pub face_of: Vec<u32>,
+    pub twin: Vec<u32>,
+    /// Edge of each half-edge, shared by twins.
+    pub edge: Vec<u32>,
+    /// One of the half-edges of each edge.
+    pub edges: Vec<u32>,
+    /// How many subdivision levels each edge stays sharp for, 0 for smooth edges and infinite for creases.
+    pub sharpness: Vec<f32>,
+}
+impl HalfEdgeMesh {
+    /// The polygons of the triangle submeshes of `mesh`, or its triangles when `polygons` is false.
+    /// Positions with the same value become the same vertex, so that parts split at texture seams stay connected.
+    /// Polygons whose outline can't be rebuilt from their triangles are kept as triangles.
+    /// Also returns the vertex of each position.
+    pub fn from_mesh(
+        mesh: &Mesh,
+        submeshes: &[Submesh],
+        polygons: bool,
+    ) -> (HalfEdgeMesh, Vec<u32>) {
+        let mut result = HalfEdgeMesh {
+            face_offsets: vec![0],
+            ..HalfEdgeMesh::default()
+        };
+
+        let mut vertices: HashMap<[u32; 3], u32> = HashMap::new();
+        let mut vertex_of_position = Vec::with_capacity(mesh.positions.len());
+        for (i, p) in mesh.positions.iter().enumerate() {
+            let key = [
+                (p[0] + 0.0).to_bits(),
+                (p[1] + 0.0).to_bits(),
+                (p[2] + 0.0).to_bits(),
+            ];
+            let next = vertices.len() as u32;
+            let vertex = *vertices.entry(key).or_insert(next);
+            if vertex == next {
+                result.positions.push(*p);
+                if let Some(color) = mesh.colors.get(i) {
+                    result.colors.push(*color);
+                }
+            }
+            vertex_of_position.push(vertex);
+        }
+
+        let mut submesh_of_triangle = vec![NONE; mesh.triangles_count()];
+        for (s, submesh) in submeshes.iter().enumerate() {
+            if submesh.primitive == Primitive::Triangles {
+                let triangles = submesh.start / 3..(submesh.start + submesh.count) / 3;
+                for triangle in submesh_of_triangle[triangles].iter_mut() {
+                    *triangle = s as u32;
+                }
+            }
+        }
+
+        let has_uvs = mesh.corners.iter().any(|c| c.uv.is_some());
+        let ranges = if polygons {
+            mesh.polygon_ranges()
+        } else {
+            (0..mesh.triangles_count()).map(|t| (t, 1)).collect()
+        };
+        let mut outline: Vec<usize> = Vec::new();
+        for (first, count) in ranges {
+            if submesh_of_triangle[first] == NONE {
+                continue;
+            }
+            let vertex = |c: usize| vertex_of_position[mesh.corners[c].position as usize];
+            outline.clear();
+            let polygon = count > 1 && polygon_outline(first, count, &vertex, &mut outline);
+            if !polygon {
+                outline.clear();
+                outline.extend(3 * first..3 * (first + count));
+            }
+            let size = if polygon { outline.len() } else { 3 };
+            for face in outline.chunks(size) {
+                for &c in face.iter() {
+                    result.vertex.push(vertex(c));
+                    if has_uvs {
+                        result.uvs.push(mesh.corner_uv(c).unwrap_or([0.0, 0.0]));
+                    }
+                }
+                result.face_offsets.push(result.vertex.len());
+                result.smoothing_groups.push(mesh.smoothing_groups[first]);
+                result.submeshes.push(submesh_of_triangle[first]);
+            }
+        }
+        let sharpness = vec![0.0; result.vertex.len()];
+        result.link(&sharpness);
+        (result, vertex_of_position)
+    }
+
+    /// The faces split into triangles, with the texture coordinates of the corners, the smoothing groups of the faces
+    /// and no normals. Faces are in the same order, so that the triangles of each submesh stay together:
+    /// `ranges` has the first corner and the number of corners of each of the `submeshes`.
+    pub fn to_mesh(&self, submeshes: usize) -> (Mesh, Vec<(usize, usize)>) {
+        let mut mesh = Mesh {
+            positions: self.positions.clone(),
+            colors: self.colors.clone(),
+            ..Mesh::default()
+        };
+        let mut ranges: Vec<Option<(usize, usize)>> = vec![None; submeshes];
+        let mut face_positions: Vec<[f32; 3]> = Vec::new();
+        for f in 0..self.faces_count() {
+            let face = self.face(f);
+            let corners: Vec<Corner> = face
+                .clone()
+                .map(|h| {
+                    let uv = if self.uvs.is_empty() {
+                        None
+                    } else {
+                        mesh.uvs.push(self.uvs[h]);
+                        Some(mesh.uvs.len() as u32 - 1)
+                    };
+                    Corner {
+                        position: self.vertex[h],
+                        uv,
+                        normal: None,
+                    }
+                })
+                .collect();
+            face_positions.clear();
+            face_positions.extend(face.map(|h| self.positions[self.vertex[h] as usize]));
+
+            let start = mesh.corners.len();
+            for (a, b, c) in triangulate::triangulate(&face_positions) {
+                mesh.push_triangle(
+                    [corners[a], corners[b], corners[c]],
+                    self.smoothing_groups[f],
+                );
+            }
+            let triangles = (mesh.corners.len() - start) / 3;
+            if triangles > 0 {
+                mesh.polygons.push(triangles as u32);
+            }
+            let range = &mut ranges[self.submeshes[f] as usize];
+            *range = Some(match *range {
+                Some((first, _)) => (first, mesh.corners.len() - first),
+                None => (start, mesh.corners.len() - start),
+            });
+        }
+        let end = mesh.corners.len();
+        let ranges = ranges
+            .iter()
+            .map(|range| range.unwrap_or((end, 0)))
+            .collect();
+        (mesh, ranges)
+    }
+
+    pub fn faces_count(&self) -> usize {
+        self.face_offsets.len() - 1
+    }
+
+    /// The half-edges of a face.
+    pub fn face(&self, f: usize) -> Range<usize> {
+        self.face_offsets[f]..self.face_offsets[f + 1]
+    }
+
+    pub fn next(&self, h: usize) -> usize {
+        let face = self.face(self.face_of[h] as usize);
+        if h + 1 < face.end {
+            h + 1
+        } else {
+            face.start
+        }
+    }
+
+    pub fn previous(&self, h: usize) -> usize {
+        let face = self.face(self.face_of[h] as usize);
+        if h > face.start {
+            h - 1
+        } else {
+            face.end - 1
+        }
+    }
+
+    /// The vertices at the ends of an edge.
+    pub fn edge_vertices(&self, e: usize) -> (usize, usize) {
+        let h = self.edges[e] as usize;
+        (self.vertex[h] as usize, self.vertex[self.next(h)] as usize)
+    }
+
+    pub fn is_border(&self, e: usize) -> bool {
+        self.twin[self.edges[e] as usize] == NONE
+    }
+
+    /// Sharpness of an edge, borders are infinitely sharp.
+    pub fn edge_sharpness(&self, e: usize) -> f32 {
+        if self.is_border(e) {
+            f32::INFINITY
+        } else {
+            self.sharpness[e]
+        }
+    }
+
+    /// The edges and the faces around each vertex, in no particular order.
+    pub fn vertex_neighbourhoods(&self) -> (Vec<Vec<u32>>, Vec<Vec<u32>>) {
+        let mut edges = vec![Vec::new(); self.positions.len()];
+        let mut faces = vec![Vec::new(); self.positions.len()];
+        for e in 0..self.edges.len() {
+            let (a, b) = self.edge_vertices(e);
+            edges[a].push(e as u32);
+            if b != a {
+                edges[b].push(e as u32);
+            }
+        }
+        for (h, &v) in self.vertex.iter().enumerate() {
+            faces[v as usize].push(self.face_of[h]);
+        }
+        (edges, faces)
+    }
+
+    /// Pair the half-edges into edges, once the faces are set. The sharpness of each edge is the highest
+    /// of its half-edges in `sharpness`.
+    pub fn link(&mut self, sharpness: &[f32]) {
+        let count = self.vertex.len();
+        self.face_of = vec![0; count];
+        for f in 0..self.faces_count() {
+            for h in self.face(f) {
+                self.face_of[h] = f as u32;
+            }
+        }
+
+        // half-edges by their ends, NONE when more than one goes the same way
+        let mut directed: HashMap<(u32, u32), u32> = HashMap::with_capacity(count);
+        for h in 0..count {
+            let key = (self.vertex[h], self.vertex[self.next(h)]);
+            directed
+                .entry(key)
+                .and_modify(|other| *other = NONE)
+                .or_insert(h as u32);
+        }
+        self.twin = vec![NONE; count];
+        for h in 0..count {
+            let (a, b) = (self.vertex[h], self.vertex[self.next(h)]);
+            if let (Some(&this), Some(&other)) = (directed.get(&(a, b)), directed.get(&(b, a))) {
+                if this != NONE && other != NONE && a != b {
+                    self.twin[h] = other;
+                }
+            }
+        }
+
+        self.edge = vec![NONE; count];
+        self.edges.clear();
+        self.sharpness.clear();
+        for h in 0..count {
+            if self.edge[h] != NONE {
+                continue;
+            }
+            let e = self.edges.len() as u32;
+            self.edge[h] = e;
+            self.edges.push(h as u32);
+            let twin = self.twin[h];
+            if twin == NONE {
+                self.sharpness.push(sharpness[h]);
+            } else {
+                self.edge[twin as usize] = e;
+                self.sharpness
+                    .push(sharpness[h].max(sharpness[twin as usize]));
+            }
+        }
+    }
+}
+
+/// The corners of a polygon in order, from its `count` triangles starting at `first`: the sides of the triangles
+/// that aren't shared with another triangle of the polygon. False when they don't make a single loop.
+fn polygon_outline(
+    first: usize,
+    count: usize,
+    vertex: &dyn Fn(usize) -> u32,
+    outline: &mut Vec<usize>,
+) -> bool {
+    let corners = 3 * first..3 * (first + count);
+    let side = |c: usize| {
+        let next = 3 * (c / 3) + (c + 1) % 3;
+        (vertex(c), vertex(next))
+    };
+    let mut starts: HashMap<u32, usize> = HashMap::new();
+    let mut sides = 0;
+    for c in corners.clone() {
+        let (a, b) = side(c);
+        // diagonals are walked both ways
+        if corners.clone().any(|other| side(other) == (b, a)) {
+            continue;
+        }
+        if starts.insert(a, c).is_some() {
+            return false;
+        }
+        sides += 1;
+    }
+    let start = match starts.values().min() {
+        Some(&start) => start,
+        None => return false,
+    };
+    let mut c = start;
+    loop {
+        outline.push(c);
+        c = match starts.get(&side(c).1) {
+            Some(&next) => next,
+            None => return false,
+        };
+        if c == start || outline.len() > sides {
+            break;
+        }
+    }
+    outline.len() == sides && sides == count + 2
+}
@@ -0,0 +1,75 @@
+//! Description of the vertices built by obj: which attributes they have, where each one is and how big a vertex is,
+//! so that a VertexBufferLayout can be made to match them.
+
+/// One attribute of the interleaved vertices, made of `components` f32 values starting `offset` bytes into a vertex.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute {
+    /// name of the vertex shader input that reads the attribute
+    pub name: &'static str,
+    pub components: usize,
+    pub offset: usize,
+}
+
+pub const POSITION: &str = "a_Position";
+pub const TEXTURE_COORDINATES: &str = "a_TexCoords";
+pub const NORMAL: &str = "a_Normal";
+pub const TANGENT: &str = "a_Tangent";
+pub const COLOR: &str = "a_Color";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexLayout {
+    pub attributes: Vec<VertexAttribute>,
+    /// bytes per vertex
+    pub stride: usize,
+}
+impl VertexLayout {
+    /// The layout of vertices with the requested attributes, in the order obj writes them.
+    pub fn new(
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> VertexLayout {
+        let mut layout = VertexLayout {
+            attributes: Vec::new(),
+            stride: 0,
+        };
+        layout.push(POSITION, 3, true);
+        layout.push(TEXTURE_COORDINATES, 2, use_texture_coordinates);
+        layout.push(NORMAL, 3, use_normals);
+        layout.push(TANGENT, 4, use_tangents);
+        layout.push(COLOR, 3, use_colors);
+        layout
+    }
+
+    fn push(&mut self, name: &'static str, components: usize, used: bool) {
+        if used {
+            self.attributes.push(VertexAttribute {
+                name,
+                components,
+                offset: self.stride,
+            });
+            self.stride += components * std::mem::size_of::<f32>();
+        }
+    }
+
+    /// Floats per vertex.
+    pub fn floats(&self) -> usize {
+        self.stride / std::mem::size_of::<f32>()
+    }
+
+    /// The attributes that the vertices don't have, with the constant value the shaders should read instead.
+    pub fn missing(&self) -> Vec<(&'static str, [f32; 4])> {
+        let defaults = [
+            (TEXTURE_COORDINATES, [0.0, 0.0, 0.0, 1.0]),
+            (NORMAL, [0.0, 0.0, 1.0, 1.0]),
+            (TANGENT, [1.0, 0.0, 0.0, 1.0]),
+            (COLOR, [1.0, 1.0, 1.0, 1.0]),
+        ];
+        defaults
+            .iter()
+            .filter(|(name, _)| !self.attributes.iter().any(|a| a.name == *name))
+            .copied()
+            .collect()
+    }
+}
@@ -0,0 +1,123 @@
+//! Small vector helpers shared by the mesh processing code.
+
+pub fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+/// Unit vector with the direction of `a`, or `a` itself when it has no length.
+pub fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = length(a);
+    if length > 0.0 {
+        scale(a, 1.0 / length)
+    } else {
+        a
+    }
+}
+
+/// 4x4 matrix in column-major order: element (row, column) is `m[4 * column + row]`.
+pub type Matrix = [f32; 16];
+
+pub const IDENTITY: Matrix = [
+    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
+];
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [0.0; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            m[4 * column + row] = (0..4).map(|k| a[4 * k + row] * b[4 * column + k]).sum();
+        }
+    }
+    m
+}
+
+/// Translation, then rotation (unit quaternion x, y, z, w), then scale, applied right to left like T * R * S.
+pub fn from_translation_rotation_scale(t: [f32; 3], r: [f32; 4], s: [f32; 3]) -> Matrix {
+    let [x, y, z, w] = r;
+    let rotation = [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y + z * w),
+            2.0 * (x * z - y * w),
+        ],
+        [
+            2.0 * (x * y - z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z + x * w),
+        ],
+        [
+            2.0 * (x * z + y * w),
+            2.0 * (y * z - x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ];
+    let mut m = IDENTITY;
+    for column in 0..3 {
+        for row in 0..3 {
+            m[4 * column + row] = rotation[column][row] * s[column];
+        }
+    }
+    m[12] = t[0];
+    m[13] = t[1];
+    m[14] = t[2];
+    m
+}
+
+pub fn transform_point(m: &Matrix, p: [f32; 3]) -> [f32; 3] {
+    let mut result = [m[12], m[13], m[14]];
+    for row in 0..3 {
+        for column in 0..3 {
+            result[row] += m[4 * column + row] * p[column];
+        }
+    }
+    result
+}
+
+/// Determinant of the 3x3 linear part: negative when the matrix mirrors the space.
+pub fn determinant(m: &Matrix) -> f32 {
+    let column = |c: usize| [m[4 * c], m[4 * c + 1], m[4 * c + 2]];
+    dot(column(0), cross(column(1), column(2)))
+}
+
+/// Transform a normal with the inverse transpose of the linear part, so that it stays orthogonal to the surface.
+/// The cofactor matrix is used instead of the inverse: it differs only by a factor, and the result is normalized.
+pub fn transform_normal(m: &Matrix, n: [f32; 3]) -> [f32; 3] {
+    let column = |c: usize| [m[4 * c], m[4 * c + 1], m[4 * c + 2]];
+    let cofactors = [
+        cross(column(1), column(2)),
+        cross(column(2), column(0)),
+        cross(column(0), column(1)),
+    ];
+    let mut result = [0.0; 3];
+    for (i, cofactor) in cofactors.iter().enumerate() {
+        result = add(result, scale(*cofactor, n[i]));
+    }
+    if determinant(m) < 0.0 {
+        result = scale(result, -1.0);
+    }
+    normalize(result)
+}
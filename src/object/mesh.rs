@@ -0,0 +1,116 @@
+//! The triangle mesh kept by obj, from which the vertices sent to the GPU are built.
+
+use super::math;
+
+/// Indices of a triangle corner into the attribute lists of a Mesh.
+/// Texture coordinates and normals are optional: `f 1 2 3` and `f 1//1 2//2 3//3` are both valid faces.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Corner {
+    pub position: u32,
+    pub uv: Option<u32>,
+    pub normal: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub normals: Vec<[f32; 3]>,
+    /// Color of each position (red, green, blue from 0 to 1), empty when the file has no colors.
+    pub colors: Vec<[f32; 3]>,
+    /// Three corners for each triangle.
+    pub corners: Vec<Corner>,
+    /// Smoothing group of each triangle, 0 means that the triangle is not smoothed with its neighbours.
+    pub smoothing_groups: Vec<u32>,
+    /// Number of consecutive triangles each polygon of the file was split into, to rebuild the polygons for
+    /// subdivision. Empty when the polygons are not known: every triangle is then a face of its own.
+    pub polygons: Vec<u32>,
+    /// Vertices drawn as points, for point clouds without faces.
+    pub points: Vec<Corner>,
+}
+impl Mesh {
+    pub fn triangles_count(&self) -> usize {
+        self.corners.len() / 3
+    }
+
+    pub fn push_triangle(&mut self, corners: [Corner; 3], smoothing_group: u32) {
+        self.corners.extend_from_slice(&corners);
+        self.smoothing_groups.push(smoothing_group);
+    }
+
+    /// First triangle and number of triangles of each polygon.
+    pub fn polygon_ranges(&self) -> Vec<(usize, usize)> {
+        if self.polygons.is_empty() {
+            return (0..self.triangles_count()).map(|t| (t, 1)).collect();
+        }
+        let mut first = 0;
+        self.polygons
+            .iter()
+            .map(|&count| {
+                first += count as usize;
+                (first - count as usize, count as usize)
+            })
+            .collect()
+    }
+
+    pub fn corner_position(&self, corner: usize) -> [f32; 3] {
+        self.positions[self.corners[corner].position as usize]
+    }
+
+    pub fn corner_uv(&self, corner: usize) -> Option<[f32; 2]> {
+        self.corners[corner].uv.map(|uv| self.uvs[uv as usize])
+    }
+
+    pub fn corner_normal(&self, corner: usize) -> Option<[f32; 3]> {
+        self.corners[corner]
+            .normal
+            .map(|normal| self.normals[normal as usize])
+    }
+
+    /// Color of the position of a corner, white when the mesh has no colors.
+    pub fn corner_color(&self, corner: usize) -> [f32; 3] {
+        self.colors
+            .get(self.corners[corner].position as usize)
+            .copied()
+            .unwrap_or([1.0; 3])
+    }
+
+    /// Normal of a triangle following its winding, with a length equal to twice its area.
+    pub fn face_normal(&self, triangle: usize) -> [f32; 3] {
+        let a = self.corner_position(3 * triangle);
+        let b = self.corner_position(3 * triangle + 1);
+        let c = self.corner_position(3 * triangle + 2);
+        math::cross(math::sub(b, a), math::sub(c, a))
+    }
+
+    /// Angle of the triangle at one of its corners, in radians.
+    pub fn corner_angle(&self, corner: usize) -> f32 {
+        let triangle = corner / 3;
+        let p = self.corner_position(corner);
+        let next = self.corner_position(3 * triangle + (corner + 1) % 3);
+        let prev = self.corner_position(3 * triangle + (corner + 2) % 3);
+        let e1 = math::normalize(math::sub(next, p));
+        let e2 = math::normalize(math::sub(prev, p));
+        math::dot(e1, e2).max(-1.0).min(1.0).acos()
+    }
+
+    /// For each position, the list of corners that use it.
+    /// Returned as offsets into a flat list: the corners of position p are `list[offsets[p]..offsets[p + 1]]`.
+    pub fn corners_by_position(&self) -> (Vec<usize>, Vec<usize>) {
+        let mut offsets = vec![0; self.positions.len() + 1];
+        for corner in self.corners.iter() {
+            offsets[corner.position as usize + 1] += 1;
+        }
+        for p in 0..self.positions.len() {
+            offsets[p + 1] += offsets[p];
+        }
+        let mut next = offsets.clone();
+        let mut list = vec![0; self.corners.len()];
+        for (i, corner) in self.corners.iter().enumerate() {
+            let slot = &mut next[corner.position as usize];
+            list[*slot] = i;
+            *slot += 1;
+        }
+        (offsets, list)
+    }
+}
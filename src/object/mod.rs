@@ -1,288 +1,832 @@
-use std::fs::File;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::io::BufRead;
-use std::str::FromStr;
-
-struct VertexData<'a> {
-    position: (&'a f32, &'a f32, &'a f32),
-    normal: (&'a f32, &'a f32, &'a f32),
-    uv: (&'a f32, &'a f32),
-    tangent: (&'a f32, &'a f32, &'a f32),
-    bitangent: (&'a f32, &'a f32, &'a f32),
+
+pub mod bounds;
+pub mod cache;
+pub mod error;
+pub mod gltf;
+pub mod halfedge;
+pub mod layout;
+mod math;
+pub mod mesh;
+pub mod mtl;
+pub mod normals;
+pub mod parser;
+pub mod ply;
+pub mod primitives;
+pub mod resolver;
+pub mod simplify;
+pub mod stl;
+pub mod subdivide;
+pub mod tangents;
+pub mod transform;
+pub mod triangulate;
+pub mod uv;
+pub mod validate;
+pub mod writer;
+use bounds::{BoundingSphere, Bounds};
+use error::ObjError;
+use layout::VertexLayout;
+use math::Matrix;
+use mesh::Mesh;
+use mtl::Material;
+use normals::NormalMode;
+use primitives::Shape;
+use resolver::{FileResolver, Resolver};
+use simplify::Lod;
+use subdivide::Subdivision;
+use tangents::TangentMode;
+use transform::ImportTransform;
+use uv::Projection;
+
+#[derive(Clone, Copy)]
+struct VertexData {
+    position: [f32; 3],
+    normal: [f32; 3],
+    uv: [f32; 2],
+    tangent: [f32; 4], // the bitangent is w * cross(normal, tangent.xyz)
+    color: [f32; 3],
 }
-impl VertexData<'_> {
-    pub fn push(&self, vertices: &mut Vec<f32>, use_texture_coordinates: bool, use_normals: bool, use_tangent_and_bitangent: bool) {
+impl VertexData {
+    /// Append the vertex to `vertices` with the attributes of layout::VertexLayout::new, in the same order.
+    pub fn push(
+        &self,
+        vertices: &mut Vec<f32>,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) {
         // position attribute
-        vertices.push(*self.position.0);
-        vertices.push(*self.position.1);
-        vertices.push(*self.position.2);
+        vertices.extend_from_slice(&self.position);
         // texture_coordinates attribute
         if use_texture_coordinates {
-            vertices.push(*self.uv.0);
-            vertices.push(*self.uv.1);
+            vertices.extend_from_slice(&self.uv);
         }
         // normal attribute
         if use_normals {
-            vertices.push(*self.normal.0);
-            vertices.push(*self.normal.1);
-            vertices.push(*self.normal.2);
+            vertices.extend_from_slice(&self.normal);
         }
 
-        if use_tangent_and_bitangent {
-            vertices.push(*self.tangent.0);
-            vertices.push(*self.tangent.1);
-            vertices.push(*self.tangent.2);
-            vertices.push(*self.bitangent.0);
-            vertices.push(*self.bitangent.1);
-            vertices.push(*self.bitangent.2);
+        // tangent attribute
+        if use_tangents {
+            vertices.extend_from_slice(&self.tangent);
+        }
+
+        // color attribute
+        if use_colors {
+            vertices.extend_from_slice(&self.color);
+        }
+    }
+}
+
+/// Extensions of the model files that obj::load can read.
+pub const MODEL_EXTENSIONS: [&str; 5] = ["obj", "gltf", "glb", "ply", "stl"];
+
+/// Whether the file looks like a model that obj::load can read.
+pub fn is_model(path: &str) -> bool {
+    extension(path).map_or(false, |e| MODEL_EXTENSIONS.contains(&e.as_str()))
+}
+
+fn extension(path: &str) -> Option<String> {
+    std::path::Path::new(path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+}
+
+/// Floats of a vertex compared bit by bit, so that they can be used as keys in a HashMap.
+struct VertexKey<'a>(&'a [f32]);
+impl VertexKey<'_> {
+    fn bits(value: f32) -> u32 {
+        // 0.0 and -0.0 are the same vertex
+        if value == 0.0 {
+            0
+        } else {
+            value.to_bits()
+        }
+    }
+}
+impl PartialEq for VertexKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(other.0.iter())
+                .all(|(a, b)| Self::bits(*a) == Self::bits(*b))
+    }
+}
+impl Eq for VertexKey<'_> {}
+impl Hash for VertexKey<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in self.0 {
+            Self::bits(*value).hash(state);
+        }
+    }
+}
+
+/// How the vertices of a submesh are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Primitive {
+    Triangles,
+    Points,
+}
+
+/// A range of consecutive triangles (or points) of the same object and group, drawn with the same material.
+/// `start` and `count` are measured in vertices of obj.vertices, or in indices after build_indices:
+/// the vertices of the triangle corners come first, then those of the points.
+#[derive(Clone, Debug)]
+pub struct Submesh {
+    pub object: Option<String>,  // set by `o`
+    pub group: Option<String>,   // set by `g`
+    pub material: Option<usize>, // index in obj.materials
+    pub primitive: Primitive,
+    pub start: usize,
+    pub count: usize,
+    pub visible: bool,
+}
+impl Submesh {
+    /// Name of the part this submesh belongs to: "object/group", or whichever of the two is defined.
+    pub fn name(&self) -> String {
+        match (&self.object, &self.group) {
+            (Some(object), Some(group)) => format!("{}/{}", object, group),
+            (Some(name), None) | (None, Some(name)) => name.clone(),
+            (None, None) => String::from("default"),
         }
     }
 }
 
 pub struct obj {
+    mesh: Mesh,
     vertices: Vec<f32>,
+    indices: Option<Vec<u32>>,
+    stride: usize, // floats per vertex
+    use_texture_coordinates: bool,
+    use_normals: bool,
+    use_tangents: bool,
+    use_colors: bool,
+    crease_angle: Option<f32>, // radians
+    normal_mode: NormalMode,
+    tangent_mode: TangentMode,
+    materials: Vec<Material>,
+    submeshes: Vec<Submesh>,
+    /// The mesh and submeshes before subdivision, while the model is subdivided.
+    cage: Option<(Mesh, Vec<Submesh>)>,
 }
 impl obj {
+    /// An obj without any vertex, useful as a placeholder when nothing could be loaded. Its (empty) indices are built.
+    pub fn empty() -> obj {
+        obj {
+            mesh: Mesh::default(),
+            vertices: Vec::new(),
+            indices: Some(Vec::new()),
+            stride: 3,
+            use_texture_coordinates: false,
+            use_normals: false,
+            use_tangents: false,
+            use_colors: false,
+            crease_angle: None,
+            normal_mode: NormalMode::File,
+            tangent_mode: TangentMode::Smooth,
+            materials: Vec::new(),
+            submeshes: Vec::new(),
+            cage: None,
+        }
+    }
+
+    /// Load a model in any of the MODEL_EXTENSIONS formats, chosen by the extension of the file.
+    /// .obj files go through load_cached, the indices are always built.
+    pub fn load(
+        path: &str,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        match extension(path).as_deref() {
+            Some("gltf") | Some("glb") | Some("ply") | Some("stl") => {
+                let data = std::fs::read(path).map_err(|err| ObjError::io(path, 0, err))?;
+                obj::from_bytes(
+                    &data,
+                    path,
+                    &FileResolver::next_to(path),
+                    use_texture_coordinates,
+                    use_normals,
+                    use_tangents,
+                    use_colors,
+                )
+            }
+            _ => obj::load_cached(
+                path,
+                use_texture_coordinates,
+                use_normals,
+                use_tangents,
+                use_colors,
+            ),
+        }
+    }
+
+    /// Load a model from `reader`, like from_bytes. The whole content is read before parsing it.
+    pub fn from_reader<R: BufRead>(
+        mut reader: R,
+        name: &str,
+        resolver: &dyn Resolver,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .map_err(|err| ObjError::io(name, 0, err))?;
+        obj::from_bytes(
+            &data,
+            name,
+            resolver,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )
+    }
+
+    /// Load a model in any of the MODEL_EXTENSIONS formats from its content, `name` gives the format with its
+    /// extension and is used in errors. The files it references are read through `resolver`.
+    /// Nothing is cached, the indices are always built.
+    pub fn from_bytes(
+        data: &[u8],
+        name: &str,
+        resolver: &dyn Resolver,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        let (data, crease_angle) = match extension(name).as_deref() {
+            Some("gltf") | Some("glb") => (gltf::load(data, name, resolver)?, None),
+            Some("ply") => (ply::load(data, name)?, None),
+            Some("stl") => (
+                stl::load(data, name, stl::WELD_TOLERANCE)?,
+                Some(stl::CREASE_ANGLE.to_radians()),
+            ),
+            _ => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                (parser::parse(data, name, resolver, threads)?, None)
+            }
+        };
+        let mut obj = obj::from_parts(
+            data,
+            crease_angle,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        );
+        obj.build_indices();
+        Ok(obj)
+    }
+
+    /// A generated mesh instead of a loaded one, see primitives::generate. The indices are built.
+    pub fn primitive(
+        shape: Shape,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> obj {
+        let mut obj = obj::from_parts(
+            primitives::generate(shape),
+            None,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        );
+        obj.build_indices();
+        obj
+    }
+
     /// Load the data from an .obj file into and obj struct.
-    /// The order of the attibutes in obj.vertices is: position, texture_coordinates (if requested), normal (if requested)
+    /// The order of the attibutes in obj.vertices is: position, texture_coordinates (if requested), normal (if requested),
+    /// tangent (if requested, four values: the last one is the sign of the bitangent), color (if requested, white for
+    /// meshes without colors)
+    /// Malformed files are reported through an ObjError pointing to the offending line and column.
+    /// Materials come from the .mtl files referenced with `mtllib`, and faces are split into one submesh
+    /// each time `o`, `g` or `usemtl` changes the object, the group or the material.
+    /// Corners without a normal get a smooth one generated from the faces around them, following the
+    /// smoothing groups set by `s` (a file without `s` statements is smoothed as a whole).
+    /// The file is read at once and parsed on all the available cores, see parser::parse.
     pub fn load_new(
         path: &str,
         use_texture_coordinates: bool,
         use_normals: bool,
-        use_tangent_and_bitangent: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        let data = std::fs::read(path).map_err(|err| ObjError::io(path, 0, err))?;
+        obj::from_data(
+            &data,
+            path,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )
+    }
+
+    /// Like load_new, but reads the binary cache written next to the file when it is still valid,
+    /// and writes it after parsing the file otherwise (see cache). The indices are always built.
+    /// Problems with the cache are only reported, falling back to the .obj file.
+    pub fn load_cached(
+        path: &str,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        match cache::read(
+            path,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        ) {
+            Ok(Some(mut obj)) => {
+                obj.build_indices();
+                return Ok(obj);
+            }
+            Ok(None) => {}
+            Err(err) => println!("WARN: {}: {}", cache::cache_path(path).display(), err),
+        }
+
+        let data = std::fs::read(path).map_err(|err| ObjError::io(path, 0, err))?;
+        let mut obj = obj::from_data(
+            &data,
+            path,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )?;
+        obj.build_indices();
+        if let Err(err) = cache::write(&obj, path, cache::hash(&data)) {
+            println!("WARN: {}: {}", cache::cache_path(path).display(), err);
+        }
+        Ok(obj)
+    }
+
+    /// Build an obj from the content of the .obj file at `path`, the materials are found next to it.
+    fn from_data(
+        data: &[u8],
+        path: &str,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> Result<obj, ObjError> {
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let resolver = FileResolver::next_to(path);
+        Ok(obj::from_parts(
+            parser::parse(data, path, &resolver, threads)?,
+            None,
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        ))
+    }
+
+    /// Build the vertices of a mesh read by one of the loaders, see set_crease_angle for `crease_angle` (in radians).
+    fn from_parts(
+        data: parser::ObjData,
+        crease_angle: Option<f32>,
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
     ) -> obj {
-        let mut vertices: Vec<f32> = Vec::new();
+        let parser::ObjData {
+            mut mesh,
+            materials,
+            mut submeshes,
+        } = data;
+        // the vertices can't be built with them
+        let (triangles, points) = validate::remove_bad_references(&mut mesh, &mut submeshes);
+        if triangles + points > 0 {
+            println!(
+                "WARN: removed {} triangles and {} points referencing missing elements",
+                triangles, points
+            );
+        }
+
+        let mut obj = obj {
+            mesh,
+            vertices: Vec::new(),
+            indices: None,
+            stride: obj::stride_of(
+                use_texture_coordinates,
+                use_normals,
+                use_tangents,
+                use_colors,
+            ),
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+            crease_angle,
+            normal_mode: NormalMode::File,
+            tangent_mode: TangentMode::Smooth,
+            materials,
+            submeshes,
+            cage: None,
+        };
+        obj.build_vertices();
+        obj
+    }
+
+    /// Floats per vertex with the given attributes.
+    fn stride_of(
+        use_texture_coordinates: bool,
+        use_normals: bool,
+        use_tangents: bool,
+        use_colors: bool,
+    ) -> usize {
+        VertexLayout::new(
+            use_texture_coordinates,
+            use_normals,
+            use_tangents,
+            use_colors,
+        )
+        .floats()
+    }
+
+    /// The attributes of the vertices in obj.vertices, to build a VertexBufferLayout.
+    pub fn layout(&self) -> VertexLayout {
+        VertexLayout::new(
+            self.use_texture_coordinates,
+            self.use_normals,
+            self.use_tangents,
+            self.use_colors,
+        )
+    }
+
+    /// Fill obj.vertices from the mesh: one vertex per triangle corner followed by one per point, with the requested
+    /// attributes. Points without a normal face the z axis.
+    /// If the indices were built they are built again.
+    fn build_vertices(&mut self) {
+        let mesh = &self.mesh;
+        let normals = self.corner_normals();
+
+        let tangents = if self.use_tangents {
+            tangents::tangents(mesh, &normals, self.tangent_mode)
+        } else {
+            Vec::new()
+        };
+
+        // USE THE DATA TO FILL THE VERTEX ARRAY WITH VALUES
+        let mut vertices: Vec<f32> =
+            Vec::with_capacity((mesh.corners.len() + mesh.points.len()) * self.stride);
+        for corner in 0..mesh.corners.len() {
+            let vertex = VertexData {
+                position: mesh.corner_position(corner),
+                normal: normals[corner],
+                uv: mesh.corner_uv(corner).unwrap_or([0.0, 0.0]),
+                tangent: tangents
+                    .get(corner)
+                    .copied()
+                    .unwrap_or([1.0, 0.0, 0.0, 1.0]),
+                color: mesh.corner_color(corner),
+            };
+            vertex.push(
+                &mut vertices,
+                self.use_texture_coordinates,
+                self.use_normals,
+                self.use_tangents,
+                self.use_colors,
+            );
+        }
+        for point in mesh.points.iter() {
+            let vertex = VertexData {
+                position: mesh.positions[point.position as usize],
+                normal: point
+                    .normal
+                    .map_or([0.0, 0.0, 1.0], |n| mesh.normals[n as usize]),
+                uv: point.uv.map_or([0.0, 0.0], |uv| mesh.uvs[uv as usize]),
+                tangent: [1.0, 0.0, 0.0, 1.0],
+                color: mesh
+                    .colors
+                    .get(point.position as usize)
+                    .copied()
+                    .unwrap_or([1.0; 3]),
+            };
+            vertex.push(
+                &mut vertices,
+                self.use_texture_coordinates,
+                self.use_normals,
+                self.use_tangents,
+                self.use_colors,
+            );
+        }
+
+        self.vertices = vertices;
+        if self.indices.is_some() {
+            self.indices = None;
+            self.build_indices();
+        }
+    }
 
-        let mut positions: Vec<f32> = Vec::new();
-        let mut normals: Vec<f32> = Vec::new();
-        let mut uvs: Vec<f32> = Vec::new();
+    /// Normal of every triangle corner, see NormalMode.
+    fn corner_normals(&self) -> Vec<[f32; 3]> {
+        let mesh = &self.mesh;
+        match self.normal_mode {
+            NormalMode::Flat => normals::flat_normals(mesh),
+            NormalMode::Smooth => normals::smooth_normals(mesh, self.crease_angle, false),
+            NormalMode::File if mesh.corners.iter().any(|c| c.normal.is_none()) => {
+                let generated = normals::smooth_normals(mesh, self.crease_angle, true);
+                (0..mesh.corners.len())
+                    .map(|c| mesh.corner_normal(c).unwrap_or(generated[c]))
+                    .collect()
+            }
+            NormalMode::File => (0..mesh.corners.len())
+                .map(|c| mesh.corner_normal(c).unwrap())
+                .collect(),
+        }
+    }
 
-        let file = File::open(path).expect("obj::load_new Failed to read file");
-        let reader = std::io::BufReader::new(file);
-        for line in reader
-            .lines()
-            .map(|line| line.expect("Failed to read line"))
+    /// Write the model to an .obj file, and its materials to a .mtl file next to it, see writer::write.
+    pub fn save(
+        &self,
+        path: &str,
+        write_materials: bool,
+        copy_textures: bool,
+    ) -> std::io::Result<()> {
+        writer::write(self, path, write_materials, copy_textures)
+    }
+
+    /// Set the maximum angle (in degrees) between two faces whose shared edge gets smoothed when generating normals,
+    /// `None` to smooth all the faces of a smoothing group. Normals read from the file are not affected.
+    pub fn set_crease_angle(&mut self, degrees: Option<f32>) {
+        self.crease_angle = degrees.map(|d| d.to_radians());
+        self.build_vertices();
+    }
+
+    /// The crease angle in degrees, see set_crease_angle.
+    pub fn get_crease_angle(&self) -> Option<f32> {
+        self.crease_angle.map(|radians| radians.to_degrees())
+    }
+
+    /// Choose where the normals come from, see NormalMode.
+    pub fn set_normal_mode(&mut self, mode: NormalMode) {
+        self.normal_mode = mode;
+        self.build_vertices();
+    }
+
+    pub fn get_normal_mode(&self) -> NormalMode {
+        self.normal_mode
+    }
+
+    /// Choose how tangents are generated, see TangentMode.
+    pub fn set_tangent_mode(&mut self, mode: TangentMode) {
+        self.tangent_mode = mode;
+        self.build_vertices();
+    }
+
+    pub fn get_tangent_mode(&self) -> TangentMode {
+        self.tangent_mode
+    }
+
+    /// Merge identical vertices, leaving one copy of each in obj.vertices and
+    /// a list of indices (three per triangle) to be drawn with an IndexBuffer.
+    /// Two vertices are identical when all of their attributes are.
+    pub fn build_indices(&mut self) {
+        if self.indices.is_some() {
+            return;
+        }
+
+        let mut unique: Vec<f32> = Vec::new();
+        let mut indices: Vec<u32> = Vec::with_capacity(self.get_vertices_count());
         {
-            let mut words = line.split_whitespace();
-            match words.next() {
-                Some("o") => {
-                    // println!("object");
-                }
-                Some("v") => {
-                    let vert = (words.next(), words.next(), words.next());
-                    match vert {
-                        (Some(x), Some(y), Some(z)) => {
-                            let (x, y, z) = (
-                                f32::from_str(x).unwrap(),
-                                f32::from_str(y).unwrap(),
-                                f32::from_str(z).unwrap(),
-                            );
-
-                            positions.push(x); // Px
-                            positions.push(y); // Py
-                            positions.push(z); // Pz
-                        }
-                        _ => {
-                            println!(
-                                "obj::load_new Each vertex has to be made of three values. Found: {:?}",
-                                vert
-                            );
-                        }
-                    }
-                }
-                Some("vt") => {
-                    if use_texture_coordinates {
-                        let uv = (words.next(), words.next());
-                        match uv {
-                            (Some(x), Some(y)) => {
-                                uvs.push(f32::from_str(x).unwrap()); // Tx
-                                uvs.push(f32::from_str(y).unwrap()); // Ty
-                            }
-                            _ => {
-                                println!(
-                                "obj::load_new Texture coordinates have to be made of two values. Found: {:?}",
-                                uv
-                            );
-                            }
-                        }
-                    }
-                }
-                Some("vn") => {
-                    if use_normals {
-                        let norm = (words.next(), words.next(), words.next());
-                        match norm {
-                            (Some(x), Some(y), Some(z)) => {
-                                normals.push(f32::from_str(x).unwrap()); // Nx
-                                normals.push(f32::from_str(y).unwrap()); // Ny
-                                normals.push(f32::from_str(z).unwrap()); // Nz
-                            }
-                            _ => {
-                                println!(
-                                "obj::load_new Normals have to be made of three values. Found: {:?}",
-                                norm
-                            );
-                            }
-                        }
-                    }
-                }
-                Some("f") => {
-                    let face = (words.next(), words.next(), words.next(), words.next());
-
-                    // USE THE DATA TO FILL THE VERTEX ARRAY WITH VALUES
-                    let mut data_to_vertex = |data: &mut std::str::Split<&str>| -> VertexData {
-                        let pi = usize::from_str(data.next().unwrap_or(""))
-                            .unwrap_or_else(|_| 1 as usize)
-                            - 1;
-                        let ti = usize::from_str(data.next().unwrap_or(""))
-                            .unwrap_or_else(|_| 1 as usize)
-                            - 1;
-                        let ni = usize::from_str(data.next().unwrap_or(""))
-                            .unwrap_or_else(|_| 1 as usize)
-                            - 1;
-
-                        VertexData {
-                            position: (
-                                positions.get(3 * pi + 0).unwrap(), 
-                                positions.get(3 * pi + 1).unwrap(), 
-                                positions.get(3 * pi + 2).unwrap()
-                            ),
-                            normal: (
-                                normals.get(3 * ni + 0).unwrap(),
-                                normals.get(3 * ni + 1).unwrap(),
-                                normals.get(3 * ni + 2).unwrap(),
-                            ),
-                            uv: (
-                                uvs.get(2 * ti + 0).unwrap(), 
-                                uvs.get(2 * ti + 1).unwrap()
-                            ),
-                            tangent: (&0.0, &0.0, &0.0),
-                            bitangent: (&0.0, &0.0, &0.0),
-                        }
-                    };
-
-                    let calculate_t_and_bt =
-                        |v1: &VertexData,
-                         v2: &VertexData,
-                         v3: &VertexData|
-                         -> ((f32, f32, f32), (f32, f32, f32)) {
-                            let edge1 = (
-                                v2.position.0 - v1.position.0,
-                                v2.position.1 - v1.position.1,
-                                v2.position.2 - v1.position.2,
-                            );
-                            let edge2 = (
-                                v3.position.0 - v1.position.0,
-                                v3.position.1 - v1.position.1,
-                                v3.position.2 - v1.position.2,
-                            );
-                            let d_uv1 = (v2.uv.0 - v1.uv.0, v2.uv.1 - v1.uv.1);
-                            let d_uv2 = (v3.uv.0 - v1.uv.0, v3.uv.1 - v1.uv.1);
-
-                            let f = 1.0 / (d_uv1.0 * d_uv2.1 - d_uv2.0 * d_uv1.1);
-
-                            let tangent = (
-                                f * (d_uv2.1 * edge1.0 - d_uv1.1 * edge2.0),
-                                f * (d_uv2.1 * edge1.1 - d_uv1.1 * edge2.1),
-                                f * (d_uv2.1 * edge1.2 - d_uv1.1 * edge2.2),
-                            );
-                            let bitangent = (
-                                f * (-d_uv2.0 * edge1.0 + d_uv1.0 * edge2.0),
-                                f * (-d_uv2.0 * edge1.1 + d_uv1.0 * edge2.1),
-                                f * (-d_uv2.0 * edge1.2 + d_uv1.0 * edge2.2),
-                            );
-
-                            return (tangent, bitangent);
-                        };
-
-                    match face {
-                        (Some(v1), Some(v2), Some(v3), None) => {
-                            let mut data1 = v1.split("/");
-                            let mut data2 = v2.split("/");
-                            let mut data3 = v3.split("/");
-
-                            let mut vertex1 = data_to_vertex(&mut data1);
-                            let mut vertex2 = data_to_vertex(&mut data2);
-                            let mut vertex3 = data_to_vertex(&mut data3);
-
-                            // calculate the tangent and bitangent TODO
-                            let (tangent, bitangent) = calculate_t_and_bt(&vertex1, &vertex2, &vertex3);
-
-                            // asign the calculated tangent and bitangent to each vertex
-                            vertex1.tangent = (&tangent.0, &tangent.1, &tangent.2);
-                            vertex2.tangent = (&tangent.0, &tangent.1, &tangent.2);
-                            vertex3.tangent = (&tangent.0, &tangent.1, &tangent.2);
-
-                            vertex1.bitangent = (&bitangent.0, &bitangent.1, &bitangent.2);
-                            vertex2.bitangent = (&bitangent.0, &bitangent.1, &bitangent.2);
-                            vertex3.bitangent = (&bitangent.0, &bitangent.1, &bitangent.2);
-
-                            vertex1.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex2.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex3.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-
-                        }
-                        (Some(v1), Some(v2), Some(v3), Some(v4)) => {
-                            // get quad data
-
-                            let mut data1 = v1.split("/");
-                            let mut data2 = v2.split("/");
-                            let mut data3 = v3.split("/");
-                            let mut data4 = v4.split("/");
-
-                            let mut vertex1 = data_to_vertex(&mut data1);
-                            let mut vertex2 = data_to_vertex(&mut data2);
-                            let mut vertex3 = data_to_vertex(&mut data3);
-                            let mut vertex4 = data_to_vertex(&mut data4);
-
-                            // calculate the tangent and bitangent for both triangles of the quad
-                            let (tangent1, bitangent1) = calculate_t_and_bt(&vertex1, &vertex2, &vertex3);
-                            let (tangent2, bitangent2) = calculate_t_and_bt(&vertex1, &vertex3, &vertex4);
-                            
-                            // first tris
-
-                            vertex1.tangent = (&tangent1.0, &tangent1.1, &tangent1.2);
-                            vertex2.tangent = (&tangent1.0, &tangent1.1, &tangent1.2);
-                            vertex3.tangent = (&tangent1.0, &tangent1.1, &tangent1.2);
-
-                            vertex1.bitangent = (&bitangent1.0, &bitangent1.1, &bitangent1.2);
-                            vertex2.bitangent = (&bitangent1.0, &bitangent1.1, &bitangent1.2);
-                            vertex3.bitangent = (&bitangent1.0, &bitangent1.1, &bitangent1.2);
-
-                            vertex1.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex2.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex3.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-
-                            // second tris
-
-                            vertex1.tangent = (&tangent2.0, &tangent2.1, &tangent2.2);
-                            vertex3.tangent = (&tangent2.0, &tangent2.1, &tangent2.2);
-                            vertex4.tangent = (&tangent2.0, &tangent2.1, &tangent2.2);
-
-                            vertex1.bitangent = (&bitangent2.0, &bitangent2.1, &bitangent2.2);
-                            vertex3.bitangent = (&bitangent2.0, &bitangent2.1, &bitangent2.2);
-                            vertex4.bitangent = (&bitangent2.0, &bitangent2.1, &bitangent2.2);
-
-                            vertex1.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex3.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                            vertex4.push(&mut vertices, use_texture_coordinates, use_normals, use_tangent_and_bitangent);
-                        }
-                        _ => {
-                            println!("obj::load_new Only faces with 3 and 4 vertices are accepted")
-                        }
-                    }
-                }
-                None => {}
-                _ => {}
+            let mut seen: HashMap<VertexKey, u32> = HashMap::new();
+            for vertex in self.vertices.chunks(self.stride) {
+                let next = seen.len() as u32;
+                let index = *seen.entry(VertexKey(vertex)).or_insert_with(|| {
+                    unique.extend_from_slice(vertex);
+                    next
+                });
+                indices.push(index);
             }
         }
 
-        obj { vertices }
+        self.vertices = unique;
+        self.indices = Some(indices);
     }
 
     pub fn get_vertices(&self) -> &Vec<f32> {
         &self.vertices
     }
 
+    /// Number of vertices in obj.vertices (not the number of floats).
     pub fn get_vertices_count(&self) -> usize {
-        self.vertices.len()
+        self.vertices.len() / self.stride
+    }
+
+    /// Indices of the triangles and points, empty before build_indices.
+    pub fn get_indices(&self) -> &[u32] {
+        self.indices.as_deref().unwrap_or(&[])
+    }
+
+    /// Whether the mesh has vertex colors.
+    pub fn has_colors(&self) -> bool {
+        !self.mesh.colors.is_empty()
+    }
+
+    pub fn get_materials(&self) -> &Vec<Material> {
+        &self.materials
+    }
+
+    pub fn get_submeshes(&self) -> &Vec<Submesh> {
+        &self.submeshes
+    }
+
+    /// Names of the parts of the model (objects and groups), in the order they appear in the file.
+    pub fn get_part_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for submesh in self.submeshes.iter() {
+            let name = submesh.name();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Show or hide all the submeshes of a part.
+    pub fn set_part_visible(&mut self, name: &str, visible: bool) {
+        for submesh in self.submeshes.iter_mut() {
+            if submesh.name() == name {
+                submesh.visible = visible;
+            }
+        }
+    }
+
+    pub fn is_part_visible(&self, name: &str) -> bool {
+        self.submeshes
+            .iter()
+            .any(|submesh| submesh.visible && submesh.name() == name)
+    }
+
+    /// The problems found in the mesh, see validate::validate.
+    pub fn validate(&self) -> validate::Report {
+        validate::validate(&self.mesh)
+    }
+
+    /// Fix the problems of the mesh that validate::repair can fix, and build the vertices again.
+    /// A subdivided model keeps its subdivision, which can't be removed anymore.
+    pub fn repair(&mut self) -> validate::Repair {
+        self.cage = None;
+        let repair = validate::repair(&mut self.mesh, &mut self.submeshes);
+        self.build_vertices();
+        repair
+    }
+
+    /// Apply `m` to the whole model and build the vertices again, see transform::transform.
+    pub fn transform(&mut self, m: &Matrix) {
+        transform::transform(&mut self.mesh, m);
+        if let Some((cage, _)) = &mut self.cage {
+            transform::transform(cage, m);
+        }
+        self.build_vertices();
+    }
+
+    /// Convert the model from the axes of its file, then recenter and scale it as `import` asks.
+    pub fn apply_import(&mut self, import: &ImportTransform) {
+        if !import.is_identity() {
+            let m = import.matrix(self.bounds());
+            self.transform(&m);
+        }
+    }
+
+    /// Whether the triangles have texture coordinates, from the file or generated.
+    pub fn has_texture_coordinates(&self) -> bool {
+        self.mesh.corners.iter().any(|corner| corner.uv.is_some())
+    }
+
+    /// Replace the texture coordinates with generated ones and build the vertices again, see uv::generate.
+    /// A subdivided model also gets new ones on the mesh it was subdivided from, made separately.
+    pub fn generate_uvs(&mut self, projection: Projection) {
+        uv::generate(&mut self.mesh, projection);
+        if let Some((cage, _)) = &mut self.cage {
+            uv::generate(cage, projection);
+        }
+        self.build_vertices();
+    }
+
+    /// A subdivision with `levels` of the scheme that fits the model, see Subdivision::for_mesh.
+    /// Edges at a bigger angle than the crease angle of the normals (see set_crease_angle) are creases.
+    pub fn subdivision_for(&self, levels: u32) -> Subdivision {
+        let mesh = self.cage.as_ref().map_or(&self.mesh, |(cage, _)| cage);
+        Subdivision {
+            crease_angle: self.crease_angle,
+            ..Subdivision::for_mesh(mesh, levels)
+        }
+    }
+
+    /// Show the model subdivided, or as it was loaded with None, and build the vertices again.
+    /// Subdivision always starts from the mesh as it was loaded, see subdivide::subdivide.
+    pub fn set_subdivision(&mut self, subdivision: Option<&Subdivision>) {
+        if let Some((mesh, mut submeshes)) = self.cage.take() {
+            for (submesh, current) in submeshes.iter_mut().zip(self.submeshes.iter()) {
+                submesh.visible = current.visible;
+            }
+            self.mesh = mesh;
+            self.submeshes = submeshes;
+        }
+        if let Some(subdivision) = subdivision {
+            let (mesh, submeshes) = subdivide::subdivide(&self.mesh, &self.submeshes, subdivision);
+            let cage_mesh = std::mem::replace(&mut self.mesh, mesh);
+            let cage_submeshes = std::mem::replace(&mut self.submeshes, submeshes);
+            self.cage = Some((cage_mesh, cage_submeshes));
+        }
+        self.build_vertices();
+    }
+
+    /// Bounds of the visible parts, None when nothing is visible.
+    pub fn bounds(&self) -> Option<Bounds> {
+        Bounds::new(self.visible_positions())
+    }
+
+    /// Sphere around the visible parts, None when nothing is visible.
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        BoundingSphere::new(&self.visible_positions())
+    }
+
+    /// The indices of the whole model as a level of detail, None before build_indices.
+    pub fn full_lod(&self) -> Option<Lod> {
+        let indices = self.indices.as_ref()?;
+        Some(Lod {
+            indices: indices.clone(),
+            ranges: self
+                .submeshes
+                .iter()
+                .map(|submesh| (submesh.start, submesh.count))
+                .collect(),
+            triangles: self.mesh.triangles_count(),
+            error: 0.0,
+        })
+    }
+
+    /// The model simplified to `target_triangles` or `target_error`, see simplify::simplify.
+    /// None before build_indices.
+    pub fn simplify(&self, target_triangles: usize, target_error: f32) -> Option<Lod> {
+        let full = self.full_lod()?;
+        Some(simplify::simplify(
+            &self.vertices,
+            self.stride,
+            &full,
+            &self.primitives(),
+            target_triangles,
+            target_error,
+        ))
+    }
+
+    /// Levels of detail of the model, from the most detailed to the coarsest, see simplify::lod_chain.
+    /// Empty before build_indices.
+    pub fn lod_chain(&self) -> Vec<Lod> {
+        match self.full_lod() {
+            Some(full) => {
+                simplify::lod_chain(&self.vertices, self.stride, &full, &self.primitives())
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn primitives(&self) -> Vec<Primitive> {
+        self.submeshes
+            .iter()
+            .map(|submesh| submesh.primitive)
+            .collect()
+    }
+
+    /// Positions of the corners and points of the visible submeshes.
+    fn visible_positions(&self) -> Vec<[f32; 3]> {
+        let mesh = &self.mesh;
+        let mut used = vec![false; mesh.positions.len()];
+        for submesh in self.submeshes.iter().filter(|submesh| submesh.visible) {
+            for i in submesh.start..submesh.start + submesh.count {
+                let corner = match mesh.corners.get(i) {
+                    Some(corner) => corner,
+                    None => &mesh.points[i - mesh.corners.len()],
+                };
+                used[corner.position as usize] = true;
+            }
+        }
+        mesh.positions
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| *used)
+            .map(|(p, _)| *p)
+            .collect()
     }
 }
@@ -0,0 +1,243 @@
+//! Parser for .mtl material libraries referenced by .obj files through `mtllib`.
+//! http://paulbourke.net/dataformats/mtl/
+
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use super::error::{Line, ObjError, ObjErrorKind};
+use super::resolver::{self, Resolver};
+
+/// A texture referenced by a material, with the options that were given before the file name.
+#[derive(Clone, Debug)]
+pub struct TextureMap {
+    pub path: PathBuf,
+    /// The encoded image, for textures embedded in the model file: `path` only names them in messages.
+    pub data: Option<Arc<Vec<u8>>>,
+    /// `-bm`, only meaningful for bump and normal maps
+    pub bump_multiplier: f32,
+    /// `-o`
+    pub offset: [f32; 3],
+    /// `-s`
+    pub scale: [f32; 3],
+    /// `-clamp`
+    pub clamp: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    /// Kd
+    pub diffuse: [f32; 3],
+    /// Ks
+    pub specular: [f32; 3],
+    /// Ns, from 0 to 1000
+    pub shininess: f32,
+    /// d (or 1 - Tr)
+    pub dissolve: f32,
+    pub illum: u32,
+    /// map_Kd
+    pub diffuse_map: Option<TextureMap>,
+    /// map_Bump, bump or norm
+    pub normal_map: Option<TextureMap>,
+    /// map_Ks
+    pub specular_map: Option<TextureMap>,
+    /// map_d
+    pub alpha_map: Option<TextureMap>,
+    /// Metallic-roughness parameters of glTF materials, Pm and Pr
+    pub metallic: f32,
+    pub roughness: f32,
+    /// roughness in the green channel, metalness in the blue one
+    pub metallic_roughness_map: Option<TextureMap>,
+}
+impl Material {
+    pub fn new(name: &str) -> Material {
+        Material {
+            name: String::from(name),
+            diffuse: [0.8, 0.8, 0.8],
+            specular: [0.0, 0.0, 0.0],
+            shininess: 0.0,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+            normal_map: None,
+            specular_map: None,
+            alpha_map: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            metallic_roughness_map: None,
+        }
+    }
+}
+
+/// Read all the materials defined in an .mtl library from `reader`, `path` is used in errors.
+/// Textures are resolved through `resolver`, relative to `library`: the name of the .mtl file relative to the model.
+pub fn parse<R: BufRead>(
+    reader: R,
+    path: &str,
+    library: &str,
+    resolver: &dyn Resolver,
+) -> Result<Vec<Material>, ObjError> {
+    let mut materials: Vec<Material> = Vec::new();
+    for (line_index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| ObjError::io(path, line_index + 1, err))?;
+        let ctx = Line {
+            path,
+            number: line_index + 1,
+            text: line.as_bytes(),
+        };
+
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = line.trim()["newmtl".len()..].trim();
+            materials.push(Material::new(name));
+            continue;
+        }
+
+        // every other statement belongs to the last declared material
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+        match keyword {
+            "Kd" => material.diffuse = parse_color(&ctx, &mut words, "Kd")?,
+            "Ks" => material.specular = parse_color(&ctx, &mut words, "Ks")?,
+            "Ns" => material.shininess = parse_float(&ctx, &mut words, "Ns")?,
+            "d" => material.dissolve = parse_float(&ctx, &mut words, "d")?,
+            "Tr" => material.dissolve = 1.0 - parse_float(&ctx, &mut words, "Tr")?,
+            "illum" => {
+                let value = parse_float(&ctx, &mut words, "illum")?;
+                material.illum = value as u32;
+            }
+            "map_Kd" => material.diffuse_map = Some(parse_map(&ctx, words, library, resolver)?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => {
+                material.normal_map = Some(parse_map(&ctx, words, library, resolver)?)
+            }
+            "map_Ks" => material.specular_map = Some(parse_map(&ctx, words, library, resolver)?),
+            "map_d" => material.alpha_map = Some(parse_map(&ctx, words, library, resolver)?),
+            "Pm" => material.metallic = parse_float(&ctx, &mut words, "Pm")?,
+            "Pr" => material.roughness = parse_float(&ctx, &mut words, "Pr")?,
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_float(
+    ctx: &Line,
+    words: &mut std::str::SplitWhitespace,
+    record: &'static str,
+) -> Result<f32, ObjError> {
+    let mut values = [0.0];
+    ctx.parse_floats(&mut words.map(str::as_bytes), record, &mut values)?;
+    Ok(values[0])
+}
+
+/// Colors are written as `r g b`, where g and b default to r when missing.
+fn parse_color(
+    ctx: &Line,
+    words: &mut std::str::SplitWhitespace,
+    record: &'static str,
+) -> Result<[f32; 3], ObjError> {
+    let r = parse_float(ctx, words, record)?;
+    let g = match words.next() {
+        Some(word) => ctx.parse_float(word.as_bytes())?,
+        None => r,
+    };
+    let b = match words.next() {
+        Some(word) => ctx.parse_float(word.as_bytes())?,
+        None => g,
+    };
+    Ok([r, g, b])
+}
+
+/// Parse the options of a texture statement and the file name that follows them.
+fn parse_map(
+    ctx: &Line,
+    words: std::str::SplitWhitespace,
+    library: &str,
+    resolver: &dyn Resolver,
+) -> Result<TextureMap, ObjError> {
+    let words: Vec<&str> = words.collect();
+    let mut map = TextureMap {
+        path: PathBuf::new(),
+        data: None,
+        bump_multiplier: 1.0,
+        offset: [0.0, 0.0, 0.0],
+        scale: [1.0, 1.0, 1.0],
+        clamp: false,
+    };
+
+    // values following an option: the ones that look like numbers are taken, up to `max`
+    let numbers = |start: usize, max: usize| -> usize {
+        words[start..]
+            .iter()
+            .take(max)
+            .take_while(|word| word.parse::<f32>().is_ok())
+            .count()
+    };
+
+    let mut i = 0;
+    while i < words.len() && words[i].starts_with('-') {
+        let option = words[i];
+        i += 1;
+        match option {
+            "-bm" | "-boost" => {
+                let n = numbers(i, 1);
+                if n == 1 && option == "-bm" {
+                    map.bump_multiplier = ctx.parse_float(words[i].as_bytes())?;
+                }
+                i += n;
+            }
+            "-o" | "-s" | "-t" => {
+                let n = numbers(i, 3);
+                let mut values = if option == "-s" {
+                    [1.0, 1.0, 1.0]
+                } else {
+                    [0.0, 0.0, 0.0]
+                };
+                for (j, word) in words[i..i + n].iter().enumerate() {
+                    values[j] = ctx.parse_float(word.as_bytes())?;
+                }
+                match option {
+                    "-o" => map.offset = values,
+                    "-s" => map.scale = values,
+                    _ => {}
+                }
+                i += n;
+            }
+            "-clamp" => {
+                map.clamp = words.get(i) == Some(&"on");
+                i += 1;
+            }
+            "-blendu" | "-blendv" | "-cc" | "-imfchan" | "-type" => i += 1,
+            "-mm" => i += numbers(i, 2),
+            "-texres" => i += numbers(i, 1),
+            _ => {
+                return Err(ctx.error(
+                    option.as_bytes(),
+                    ObjErrorKind::BadValue(format!("unknown texture option '{}'", option)),
+                ))
+            }
+        }
+    }
+
+    if i >= words.len() {
+        return Err(ctx.missing_values("texture map", 1, 0));
+    }
+    // the file name may contain spaces, keep it as written in the line
+    let start = ctx.column_of(words[i].as_bytes()) - 1;
+    let name = String::from_utf8_lossy(&ctx.text[start..])
+        .trim_end()
+        .replace('\\', "/");
+    let (path, data) = resolver::texture(resolver, &resolver::relative_to(library, &name));
+    map.path = path;
+    map.data = data;
+
+    Ok(map)
+}
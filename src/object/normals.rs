@@ -0,0 +1,77 @@
+//! Normal generation for meshes that don't provide their own.
+
+use super::math;
+use super::mesh::Mesh;
+
+/// Where the normals of the vertices come from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMode {
+    /// The normals of the file, and smooth normals following its smoothing groups where it has none.
+    File,
+    /// The normal of its triangle at every corner, to inspect the faceting of the mesh.
+    Flat,
+    /// Smooth normals everywhere, hard only across the crease angle: the normals and the smoothing groups of the
+    /// file are ignored.
+    Smooth,
+}
+
+/// The unit normal of its triangle for every corner of the mesh.
+pub fn flat_normals(mesh: &Mesh) -> Vec<[f32; 3]> {
+    (0..mesh.corners.len())
+        .map(|c| math::normalize(mesh.face_normal(c / 3)))
+        .collect()
+}
+
+/// Smooth normals for every corner of the mesh, weighted by the area of the triangles and by their angle at the corner.
+/// Two triangles sharing a position are smoothed together only if, with `smoothing_groups`, they belong to the same
+/// smoothing group (not 0) and, when `crease_angle` (radians) is given, if the angle between them is not bigger
+/// than it: in all other cases the edge between them stays hard.
+pub fn smooth_normals(
+    mesh: &Mesh,
+    crease_angle: Option<f32>,
+    smoothing_groups: bool,
+) -> Vec<[f32; 3]> {
+    let triangles = mesh.triangles_count();
+    let face_normals: Vec<[f32; 3]> = (0..triangles).map(|t| mesh.face_normal(t)).collect();
+    let unit_normals: Vec<[f32; 3]> = face_normals.iter().map(|n| math::normalize(*n)).collect();
+    let angles: Vec<f32> = (0..mesh.corners.len())
+        .map(|c| mesh.corner_angle(c))
+        .collect();
+    let min_cos = crease_angle.map(|angle| angle.cos());
+
+    let smooth_together = |t1: usize, t2: usize| -> bool {
+        if t1 == t2 {
+            return true;
+        }
+        let group = mesh.smoothing_groups[t1];
+        if smoothing_groups && (group == 0 || group != mesh.smoothing_groups[t2]) {
+            return false;
+        }
+        match min_cos {
+            Some(min_cos) => math::dot(unit_normals[t1], unit_normals[t2]) >= min_cos,
+            None => true,
+        }
+    };
+
+    let (offsets, list) = mesh.corners_by_position();
+    let mut normals = Vec::with_capacity(mesh.corners.len());
+    for (c, corner) in mesh.corners.iter().enumerate() {
+        let t = c / 3;
+        let p = corner.position as usize;
+
+        let mut sum = [0.0, 0.0, 0.0];
+        for &other in list[offsets[p]..offsets[p + 1]].iter() {
+            if smooth_together(t, other / 3) {
+                sum = math::add(sum, math::scale(face_normals[other / 3], angles[other]));
+            }
+        }
+
+        let normal = math::normalize(sum);
+        normals.push(if math::length(normal) > 0.0 {
+            normal
+        } else {
+            unit_normals[t]
+        });
+    }
+    normals
+}
@@ -0,0 +1,1027 @@
+//! Parser of the .obj text format, fast enough for scans of some gigabytes.
+//! The file is split into chunks of whole lines that are parsed in parallel, then the chunks are stitched together:
+//! face indices are resolved against the positions, texture coordinates and normals of the whole file,
+//! and the statements that change object, group, material and smoothing group are applied in file order.
+
+use std::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use super::error::{Line, ObjError, ObjErrorKind};
+use super::mesh::{Corner, Mesh};
+use super::mtl::{self, Material};
+use super::resolver::Resolver;
+use super::triangulate;
+use super::{Primitive, Submesh};
+
+/// Files are split in chunks of at least this many bytes, smaller ones are not worth a thread.
+const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+impl<'a> Line<'a> {
+    fn words(&self) -> Words<'a> {
+        Words { rest: self.text }
+    }
+
+    /// What follows the keyword, without the surrounding spaces: the name given to `o` or `usemtl`.
+    fn name_after(&self, keyword: &[u8]) -> String {
+        let start = keyword.as_ptr() as usize - self.text.as_ptr() as usize + keyword.len();
+        String::from(String::from_utf8_lossy(&self.text[start..]).trim())
+    }
+
+    /// Parse a face corner in one of the forms `v`, `v/vt`, `v//vn` or `v/vt/vn`.
+    /// Indices are kept as written (MISSING when absent), they are resolved once the whole file has been read.
+    fn parse_corner(&self, word: &[u8]) -> Result<[i32; 3], ObjError> {
+        let bad_index = |value: &[u8]| {
+            let value = String::from_utf8_lossy(value).into_owned();
+            self.error(word, ObjErrorKind::BadIndex(value))
+        };
+        let mut corner = [MISSING; 3];
+        for (i, value) in word.split(|&byte| byte == b'/').enumerate() {
+            if i == 3 || (i == 0 && value.is_empty()) {
+                return Err(bad_index(word));
+            }
+            if value.is_empty() {
+                continue;
+            }
+            corner[i] = parse_i32(value)
+                .filter(|&index| index != MISSING)
+                .ok_or_else(|| bad_index(value))?;
+        }
+        Ok(corner)
+    }
+}
+
+/// The words of a line, separated by ASCII whitespace.
+#[derive(Clone)]
+struct Words<'a> {
+    rest: &'a [u8],
+}
+impl<'a> Iterator for Words<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let start = self
+            .rest
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())?;
+        let rest = &self.rest[start..];
+        let end = rest
+            .iter()
+            .position(|byte| byte.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        self.rest = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+fn parse_i32(word: &[u8]) -> Option<i32> {
+    let (negative, digits) = match word.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        Some((b'+', digits)) => (false, digits),
+        _ => (false, word),
+    };
+    if digits.is_empty() || digits.len() > 10 {
+        return None;
+    }
+    let mut value: i64 = 0;
+    for &byte in digits {
+        if !byte.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + (byte - b'0') as i64;
+    }
+    let value = if negative { -value } else { value };
+    if value < i32::MIN as i64 || value > i32::MAX as i64 {
+        return None;
+    }
+    Some(value as i32)
+}
+
+const ELEMENTS: [&str; 3] = ["position", "texture coordinate", "normal"];
+/// Stands for the texture coordinate or normal index left out of a face corner.
+const MISSING: i32 = i32::MIN;
+
+/// Parse a decimal number like `-12.5e-3` without going through the generic float parser.
+/// Anything unusual (inf, nan, more than 15 significant digits, huge exponents) falls back to `f32::from_str`.
+pub fn parse_f32(word: &[u8]) -> Option<f32> {
+    let bytes = word;
+    let fallback = || std::str::from_utf8(word).ok()?.parse::<f32>().ok();
+    let mut i = 0;
+    let negative = match bytes.first() {
+        Some(b'-') => {
+            i += 1;
+            true
+        }
+        Some(b'+') => {
+            i += 1;
+            false
+        }
+        _ => false,
+    };
+
+    let mut mantissa: u64 = 0;
+    let mut significant_digits = 0;
+    let mut exponent: i32 = 0;
+    let mut any_digit = false;
+    let mut after_point = false;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        if byte.is_ascii_digit() {
+            any_digit = true;
+            mantissa = mantissa * 10 + (byte - b'0') as u64;
+            if mantissa != 0 {
+                significant_digits += 1;
+            }
+            if after_point {
+                exponent -= 1;
+            }
+            if significant_digits > 15 {
+                return fallback();
+            }
+        } else if byte == b'.' && !after_point {
+            after_point = true;
+        } else {
+            break;
+        }
+        i += 1;
+    }
+    if !any_digit {
+        return fallback();
+    }
+
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        i += 1;
+        let exponent_negative = match bytes.get(i) {
+            Some(b'-') => {
+                i += 1;
+                true
+            }
+            Some(b'+') => {
+                i += 1;
+                false
+            }
+            _ => false,
+        };
+        let start = i;
+        let mut written: i32 = 0;
+        while i < bytes.len() && bytes[i].is_ascii_digit() && written < 1000 {
+            written = written * 10 + (bytes[i] - b'0') as i32;
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        exponent += if exponent_negative { -written } else { written };
+    }
+    if i != bytes.len() {
+        return fallback();
+    }
+
+    // below 2^53 and 10^22 both numbers are exact in a f64, so the product is correctly rounded
+    const POWERS: [f64; 23] = [
+        1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+        1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+    ];
+    let value = if mantissa == 0 {
+        0.0
+    } else if (0..=22).contains(&exponent) {
+        mantissa as f64 * POWERS[exponent as usize]
+    } else if (-22..0).contains(&exponent) {
+        mantissa as f64 / POWERS[-exponent as usize]
+    } else {
+        return fallback();
+    };
+    let value = value as f32;
+    Some(if negative { -value } else { value })
+}
+
+/// Everything read from an .obj file.
+pub struct ObjData {
+    pub mesh: Mesh,
+    pub materials: Vec<Material>,
+    pub submeshes: Vec<Submesh>,
+}
+
+/// A statement that changes the state applied to the faces that follow it.
+enum Statement {
+    Object(Option<String>),
+    Group(Option<String>),
+    Material { name: String, line: usize },
+    SmoothingGroup(u32),
+    Libraries(Vec<String>),
+}
+
+/// A face as found in a chunk, its corners are `chunk.corners[previous face end..end]`.
+struct Face {
+    end: usize,
+    /// byte offset of the line in the chunk, to report errors found while resolving indices
+    offset: usize,
+    /// positions, texture coordinates and normals defined in the chunk before this face
+    counts: [u32; 3],
+}
+
+/// A vertex of a `p` statement as found in a chunk.
+struct Point {
+    corner: [i32; 3],
+    /// byte offset of the line in the chunk and index of the word in the line, to report errors
+    offset: usize,
+    word: usize,
+    /// positions, texture coordinates and normals defined in the chunk before this point
+    counts: [u32; 3],
+    /// statements of the chunk before this point
+    statements: usize,
+}
+
+/// The result of parsing a chunk of lines on its own.
+#[derive(Default)]
+struct Chunk {
+    lines: usize,
+    positions: Vec<[f32; 3]>,
+    /// one for each position once a `v` with a color is found, white for the ones without
+    colors: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    corners: Vec<[i32; 3]>,
+    faces: Vec<Face>,
+    points: Vec<Point>,
+    /// each statement comes before the face with that index
+    statements: Vec<(usize, Statement)>,
+}
+
+/// Triangles of the faces of a chunk and its points, with the corner indices resolved.
+struct Triangles {
+    corners: Vec<Corner>,
+    /// how many triangles each face of the chunk was split into
+    per_face: Vec<u32>,
+    points: Vec<Corner>,
+}
+
+/// An index pointing outside of its list: the byte offset of the line in the chunk, the word in the line,
+/// and what went wrong.
+struct IndexError {
+    offset: usize,
+    word: usize,
+    kind: ObjErrorKind,
+}
+
+/// Parse the content of an .obj file using up to `threads` threads.
+/// `path` is used for error messages, the .mtl files referenced with `mtllib` are read through `resolver`.
+pub fn parse(
+    data: &[u8],
+    path: &str,
+    resolver: &dyn Resolver,
+    threads: usize,
+) -> Result<ObjData, ObjError> {
+    let ranges = split_chunks(data, threads);
+
+    // parse every chunk on its own
+    let parsed: Vec<Result<Chunk, ObjError>> = in_parallel(&ranges, threads, |range| {
+        parse_chunk(&data[range.clone()], path)
+    });
+    let mut chunks: Vec<Chunk> = Vec::with_capacity(parsed.len());
+    let mut first_lines: Vec<usize> = Vec::with_capacity(parsed.len());
+    let mut lines = 0;
+    for chunk in parsed {
+        match chunk {
+            Ok(chunk) => {
+                first_lines.push(lines);
+                lines += chunk.lines;
+                chunks.push(chunk);
+            }
+            Err(mut err) => {
+                err.line += lines;
+                return Err(err);
+            }
+        }
+    }
+
+    // join the attributes, remembering where each chunk starts
+    let mut mesh = Mesh::default();
+    let mut offsets: Vec<[usize; 3]> = Vec::with_capacity(chunks.len());
+    let has_colors = chunks.iter().any(|chunk| !chunk.colors.is_empty());
+    for chunk in chunks.iter_mut() {
+        offsets.push([mesh.positions.len(), mesh.uvs.len(), mesh.normals.len()]);
+        if has_colors {
+            chunk.colors.resize(chunk.positions.len(), [1.0; 3]);
+            mesh.colors.append(&mut chunk.colors);
+        }
+        mesh.positions.append(&mut chunk.positions);
+        mesh.uvs.append(&mut chunk.uvs);
+        mesh.normals.append(&mut chunk.normals);
+    }
+
+    // resolve the indices and triangulate the faces
+    let jobs: Vec<usize> = (0..chunks.len()).collect();
+    let triangulated: Vec<Result<Triangles, IndexError>> = in_parallel(&jobs, threads, |&i| {
+        resolve_chunk(&chunks[i], offsets[i], &mesh.positions)
+    });
+
+    // apply the statements and collect the triangles in file order
+    let mut state = State {
+        path,
+        resolver,
+        materials: Vec::new(),
+        submeshes: Vec::new(),
+        point_submeshes: Vec::new(),
+        material: None,
+        object: None,
+        group: None,
+        smoothing_group: 1,
+    };
+    let corners_count: usize = triangulated
+        .iter()
+        .map(|triangles| triangles.as_ref().map_or(0, |t| t.corners.len()))
+        .sum();
+    mesh.corners.reserve(corners_count);
+    mesh.smoothing_groups.reserve(corners_count / 3);
+    let mut all_points: Vec<Corner> = Vec::new();
+    for (i, (chunk, triangles)) in chunks.iter().zip(triangulated).enumerate() {
+        let triangles = match triangles {
+            Ok(triangles) => triangles,
+            Err(err) => {
+                let text = &data[ranges[i].clone()];
+                return Err(index_error(err, text, first_lines[i], path));
+            }
+        };
+
+        // points are pushed with the state of the statements before them
+        let mut statements = chunk.statements.iter().enumerate().peekable();
+        let mut points = chunk.points.iter().zip(triangles.points).peekable();
+        let mut corner = 0;
+        for (face, &count) in triangles.per_face.iter().enumerate() {
+            while let Some((s, (_, statement))) =
+                statements.next_if(|(_, (before, _))| *before <= face)
+            {
+                while let Some((_, point)) = points.next_if(|(p, _)| p.statements <= s) {
+                    state.push_point(&mut all_points, point);
+                }
+                state.apply(statement, first_lines[i]);
+            }
+            let end = corner + 3 * count as usize;
+            state.push_face(&mut mesh, &triangles.corners[corner..end]);
+            corner = end;
+        }
+        for (s, (_, statement)) in statements {
+            while let Some((_, point)) = points.next_if(|(p, _)| p.statements <= s) {
+                state.push_point(&mut all_points, point);
+            }
+            state.apply(statement, first_lines[i]);
+        }
+        for (_, point) in points {
+            state.push_point(&mut all_points, point);
+        }
+    }
+
+    // the points come after all the triangle corners
+    mesh.points = all_points;
+    let mut submeshes = state.submeshes;
+    for mut submesh in state.point_submeshes {
+        submesh.start += mesh.corners.len();
+        submeshes.push(submesh);
+    }
+    Ok(ObjData {
+        mesh,
+        materials: state.materials,
+        submeshes,
+    })
+}
+
+/// Split the file in ranges of whole lines, a few for each thread so that they stay busy until the end.
+fn split_chunks(data: &[u8], threads: usize) -> Vec<Range<usize>> {
+    let count = (data.len() / MIN_CHUNK_SIZE).min(4 * threads).max(1);
+    let size = data.len() / count + 1;
+
+    let mut ranges = Vec::with_capacity(count);
+    let mut start = 0;
+    while start < data.len() {
+        let end = (start + size).min(data.len());
+        let end = match data[end..].iter().position(|&byte| byte == b'\n') {
+            Some(newline) => end + newline + 1,
+            None => data.len(),
+        };
+        ranges.push(start..end);
+        start = end;
+    }
+    if ranges.is_empty() {
+        ranges.push(0..0);
+    }
+    ranges
+}
+
+/// Call `work` on every item using up to `threads` threads, returning the results in the order of the items.
+fn in_parallel<T, R, F>(items: &[T], threads: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if threads <= 1 || items.len() <= 1 {
+        return items.iter().map(work).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Vec<Mutex<Option<R>>> = items.iter().map(|_| Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..threads.min(items.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= items.len() {
+                    break;
+                }
+                let result = work(&items[i]);
+                *results[i].lock().unwrap() = Some(result);
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|result| result.into_inner().unwrap().unwrap())
+        .collect()
+}
+
+/// Parse a chunk of lines. Line numbers in the errors count from the start of the chunk.
+fn parse_chunk(text: &[u8], path: &str) -> Result<Chunk, ObjError> {
+    let mut chunk = Chunk::default();
+
+    for line in text.split(|&byte| byte == b'\n') {
+        chunk.lines += 1;
+        let line = Line {
+            path,
+            number: chunk.lines,
+            text: line,
+        };
+
+        let mut words = line.words();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        match keyword {
+            b"v" => {
+                let mut position = [0.0; 3];
+                // Px, Py, Pz
+                line.parse_floats(&mut words, "v", &mut position)?;
+                // an optional weight, or a color written by some exporters as `v x y z r g b`
+                if words.clone().count() >= 3 {
+                    let mut color = [0.0; 3];
+                    line.parse_floats(&mut words, "v", &mut color)?;
+                    chunk.colors.resize(chunk.positions.len(), [1.0; 3]);
+                    chunk.colors.push(color);
+                } else if !chunk.colors.is_empty() {
+                    chunk.colors.push([1.0; 3]);
+                }
+                chunk.positions.push(position);
+            }
+            b"vt" => {
+                let mut uv = [0.0; 2];
+                line.parse_floats(&mut words, "vt", &mut uv)?; // Tx, Ty
+                chunk.uvs.push(uv);
+            }
+            b"vn" => {
+                let mut normal = [0.0; 3];
+                line.parse_floats(&mut words, "vn", &mut normal)?; // Nx, Ny, Nz
+                chunk.normals.push(normal);
+            }
+            b"f" => {
+                let first_corner = chunk.corners.len();
+                for word in words {
+                    let corner = line.parse_corner(word)?;
+                    chunk.corners.push(corner);
+                }
+                let arity = chunk.corners.len() - first_corner;
+                if arity < 3 {
+                    return Err(match line.words().nth(1) {
+                        Some(word) => line.error(word, ObjErrorKind::UnsupportedFaceArity(arity)),
+                        None => ObjError::new(
+                            path,
+                            line.number,
+                            0,
+                            ObjErrorKind::UnsupportedFaceArity(arity),
+                        ),
+                    });
+                }
+                chunk.faces.push(Face {
+                    end: chunk.corners.len(),
+                    offset: line.text.as_ptr() as usize - text.as_ptr() as usize,
+                    counts: [
+                        chunk.positions.len() as u32,
+                        chunk.uvs.len() as u32,
+                        chunk.normals.len() as u32,
+                    ],
+                });
+            }
+            b"p" => {
+                for (word, value) in words.enumerate() {
+                    chunk.points.push(Point {
+                        corner: line.parse_corner(value)?,
+                        offset: line.text.as_ptr() as usize - text.as_ptr() as usize,
+                        word: word + 1,
+                        counts: [
+                            chunk.positions.len() as u32,
+                            chunk.uvs.len() as u32,
+                            chunk.normals.len() as u32,
+                        ],
+                        statements: chunk.statements.len(),
+                    });
+                }
+            }
+            b"o" => {
+                let name = line.name_after(keyword);
+                let name = if name.is_empty() { None } else { Some(name) };
+                chunk
+                    .statements
+                    .push((chunk.faces.len(), Statement::Object(name)));
+            }
+            b"g" => {
+                // a face can be part of many groups: `g left arm`
+                let names: Vec<String> = words
+                    .map(|word| String::from_utf8_lossy(word).into_owned())
+                    .collect();
+                let name = if names.is_empty() || names == ["default"] {
+                    None
+                } else {
+                    Some(names.join(" "))
+                };
+                chunk
+                    .statements
+                    .push((chunk.faces.len(), Statement::Group(name)));
+            }
+            b"mtllib" => {
+                let names = words
+                    .map(|word| String::from_utf8_lossy(word).into_owned())
+                    .collect();
+                chunk
+                    .statements
+                    .push((chunk.faces.len(), Statement::Libraries(names)));
+            }
+            b"usemtl" => {
+                let name = line.name_after(keyword);
+                let number = line.number;
+                chunk.statements.push((
+                    chunk.faces.len(),
+                    Statement::Material { name, line: number },
+                ));
+            }
+            b"s" => {
+                let group = match words.next() {
+                    Some(b"off") | None => 0,
+                    Some(b"on") => 1,
+                    Some(value) => {
+                        parse_i32(value)
+                            .filter(|&group| group >= 0)
+                            .ok_or_else(|| {
+                                line.error(
+                                    value,
+                                    ObjErrorKind::BadValue(format!(
+                                        "'{}' is not a valid smoothing group",
+                                        String::from_utf8_lossy(value)
+                                    )),
+                                )
+                            })? as u32
+                    }
+                };
+                chunk
+                    .statements
+                    .push((chunk.faces.len(), Statement::SmoothingGroup(group)));
+            }
+            _ => {}
+        }
+    }
+
+    // every chunk but the last ends with a newline, which doesn't start another line
+    if text.ends_with(b"\n") {
+        chunk.lines -= 1;
+    }
+    Ok(chunk)
+}
+
+/// Resolve an index as written in the file: positive indices count from 1, negative ones count backwards
+/// from the last element defined so far. `count` is the number of elements defined before the face.
+fn resolve_index(index: i32, count: usize, element: &'static str) -> Result<u32, ObjErrorKind> {
+    let resolved = if index > 0 {
+        index as i64 - 1
+    } else {
+        count as i64 + index as i64
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(ObjErrorKind::IndexOutOfRange {
+            element,
+            index: index as i64,
+            count,
+        });
+    }
+    Ok(resolved as u32)
+}
+
+/// Triangulate the faces of a chunk, whose positions, texture coordinates and normals start at `offsets`.
+fn resolve_chunk(
+    chunk: &Chunk,
+    offsets: [usize; 3],
+    positions: &[[f32; 3]],
+) -> Result<Triangles, IndexError> {
+    let mut triangles = Triangles {
+        corners: Vec::with_capacity(3 * chunk.corners.len()),
+        per_face: Vec::with_capacity(chunk.faces.len()),
+        points: Vec::with_capacity(chunk.points.len()),
+    };
+    let mut corners: Vec<Corner> = Vec::new();
+    let mut points: Vec<[f32; 3]> = Vec::new();
+
+    let mut start = 0;
+    for face in chunk.faces.iter() {
+        corners.clear();
+        points.clear();
+        for (c, raw) in chunk.corners[start..face.end].iter().enumerate() {
+            let corner = resolve_corner(raw, offsets, face.counts).map_err(|kind| IndexError {
+                offset: face.offset,
+                word: c + 1,
+                kind,
+            })?;
+            points.push(positions[corner.position as usize]);
+            corners.push(corner);
+        }
+        start = face.end;
+
+        if corners.len() == 3 {
+            triangles.corners.extend_from_slice(&corners);
+            triangles.per_face.push(1);
+            continue;
+        }
+        let mut count = 0;
+        for (a, b, c) in triangulate::triangulate(&points) {
+            triangles
+                .corners
+                .extend_from_slice(&[corners[a], corners[b], corners[c]]);
+            count += 1;
+        }
+        triangles.per_face.push(count);
+    }
+
+    for point in chunk.points.iter() {
+        let corner =
+            resolve_corner(&point.corner, offsets, point.counts).map_err(|kind| IndexError {
+                offset: point.offset,
+                word: point.word,
+                kind,
+            })?;
+        triangles.points.push(corner);
+    }
+    Ok(triangles)
+}
+
+/// Resolve the indices of a face corner or of a point, see resolve_index.
+/// `counts` are the elements defined in the chunk before it, which starts at `offsets`.
+fn resolve_corner(
+    raw: &[i32; 3],
+    offsets: [usize; 3],
+    counts: [u32; 3],
+) -> Result<Corner, ObjErrorKind> {
+    let mut resolved = [None; 3];
+    for i in 0..3 {
+        if raw[i] != MISSING {
+            let count = offsets[i] + counts[i] as usize;
+            resolved[i] = Some(resolve_index(raw[i], count, ELEMENTS[i])?);
+        }
+    }
+    Ok(Corner {
+        position: resolved[0].unwrap(),
+        uv: resolved[1],
+        normal: resolved[2],
+    })
+}
+
+/// Turn an IndexError into an ObjError pointing to the corner or point in the file.
+fn index_error(err: IndexError, text: &[u8], first_line: usize, path: &str) -> ObjError {
+    let offset = err.offset;
+    let line = Line {
+        path,
+        number: first_line + text[..offset].iter().filter(|&&byte| byte == b'\n').count() + 1,
+        text: text[offset..]
+            .split(|&byte| byte == b'\n')
+            .next()
+            .unwrap_or(&[]),
+    };
+    match line.words().nth(err.word) {
+        Some(word) => line.error(word, err.kind),
+        None => ObjError::new(path, line.number, 0, err.kind),
+    }
+}
+
+/// Object, group, material and smoothing group applied to the faces, and what has been built so far.
+struct State<'a> {
+    path: &'a str,
+    resolver: &'a dyn Resolver,
+    materials: Vec<Material>,
+    submeshes: Vec<Submesh>,
+    /// submeshes of the points, with `start` counted from the first point
+    point_submeshes: Vec<Submesh>,
+    material: Option<usize>,
+    object: Option<String>,
+    group: Option<String>,
+    smoothing_group: u32,
+}
+impl State<'_> {
+    fn apply(&mut self, statement: &Statement, first_line: usize) {
+        match statement {
+            Statement::Object(name) => {
+                self.object = name.clone();
+                // groups belong to the object they are declared in
+                self.group = None;
+            }
+            Statement::Group(name) => self.group = name.clone(),
+            Statement::Libraries(names) => {
+                for name in names {
+                    let name = name.replace('\\', "/");
+                    let mtl_path = self.resolver.describe(&name);
+                    let loaded = self
+                        .resolver
+                        .read(&name)
+                        .map_err(|err| ObjError::io(&mtl_path, 0, err))
+                        .and_then(|data| mtl::parse(&data[..], &mtl_path, &name, self.resolver));
+                    match loaded {
+                        Ok(mut loaded) => self.materials.append(&mut loaded),
+                        // the geometry is still worth showing without its materials
+                        Err(err) => println!("WARN: {}", err),
+                    }
+                }
+            }
+            Statement::Material { name, line } => {
+                self.material = self.materials.iter().position(|m| &m.name == name);
+                if self.material.is_none() {
+                    println!(
+                        "WARN: {}:{}: material '{}' not found",
+                        self.path,
+                        first_line + line,
+                        name
+                    );
+                }
+            }
+            Statement::SmoothingGroup(group) => self.smoothing_group = *group,
+        }
+    }
+
+    /// Add the triangles of a face to the mesh, starting a new submesh if the state changed.
+    fn push_face(&mut self, mesh: &mut Mesh, corners: &[Corner]) {
+        match self.submeshes.last() {
+            Some(submesh)
+                if submesh.material == self.material
+                    && submesh.object == self.object
+                    && submesh.group == self.group => {}
+            _ => self.submeshes.push(Submesh {
+                object: self.object.clone(),
+                group: self.group.clone(),
+                material: self.material,
+                primitive: Primitive::Triangles,
+                start: mesh.corners.len(),
+                count: 0,
+                visible: true,
+            }),
+        }
+
+        for triangle in corners.chunks(3) {
+            mesh.push_triangle(
+                [triangle[0], triangle[1], triangle[2]],
+                self.smoothing_group,
+            );
+        }
+        if !corners.is_empty() {
+            mesh.polygons.push((corners.len() / 3) as u32);
+        }
+
+        if let Some(submesh) = self.submeshes.last_mut() {
+            submesh.count = mesh.corners.len() - submesh.start;
+        }
+    }
+
+    /// Add a point to the points of the file, starting a new submesh if the state changed.
+    fn push_point(&mut self, points: &mut Vec<Corner>, point: Corner) {
+        match self.point_submeshes.last() {
+            Some(submesh)
+                if submesh.material == self.material
+                    && submesh.object == self.object
+                    && submesh.group == self.group => {}
+            _ => self.point_submeshes.push(Submesh {
+                object: self.object.clone(),
+                group: self.group.clone(),
+                material: self.material,
+                primitive: Primitive::Points,
+                start: points.len(),
+                count: 0,
+                visible: true,
+            }),
+        }
+        points.push(point);
+        if let Some(submesh) = self.point_submeshes.last_mut() {
+            submesh.count += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::str::FromStr;
+
+    use super::super::error::{ObjError, ObjErrorKind};
+    use super::super::mesh::Corner;
+    use super::{parse, parse_f32, ObjData};
+
+    const POSITIONS: &str = "v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+";
+
+    fn no_files(_: &str) -> io::Result<Vec<u8>> {
+        Err(io::Error::from(io::ErrorKind::NotFound))
+    }
+
+    fn parse_text(text: &str) -> Result<ObjData, ObjError> {
+        parse(text.as_bytes(), "test.obj", &no_files, 1)
+    }
+
+    fn parse_error(text: &str) -> ObjError {
+        match parse_text(text) {
+            Ok(_) => panic!("no error in {}", text),
+            Err(err) => err,
+        }
+    }
+
+    fn corner(position: u32, uv: Option<u32>, normal: Option<u32>) -> Corner {
+        Corner {
+            position,
+            uv,
+            normal,
+        }
+    }
+
+    #[test]
+    fn negative_indices() {
+        let data = parse_text(&format!("{}f -4 -3 -2\n", POSITIONS)).unwrap();
+        assert_eq!(
+            data.mesh.corners,
+            vec![
+                corner(0, None, None),
+                corner(1, None, None),
+                corner(2, None, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_texture_coordinates() {
+        let text = format!(
+            "{}vn 0 0 1\nvn 0 0 -1\nvn 1 0 0\nf 1//3 2//2 3//1\n",
+            POSITIONS
+        );
+        let data = parse_text(&text).unwrap();
+        assert_eq!(
+            data.mesh.corners,
+            vec![
+                corner(0, None, Some(2)),
+                corner(1, None, Some(1)),
+                corner(2, None, Some(0))
+            ]
+        );
+    }
+
+    #[test]
+    fn positions_only() {
+        let data = parse_text(&format!("{}f 1 2 3\n", POSITIONS)).unwrap();
+        assert_eq!(
+            data.mesh.corners,
+            vec![
+                corner(0, None, None),
+                corner(1, None, None),
+                corner(2, None, None)
+            ]
+        );
+        assert!(data.mesh.uvs.is_empty() && data.mesh.normals.is_empty());
+    }
+
+    #[test]
+    fn index_zero() {
+        let err = parse_error(&format!("{}f 0 1 2\n", POSITIONS));
+        assert!(matches!(
+            err.kind,
+            ObjErrorKind::BadIndex(_) | ObjErrorKind::IndexOutOfRange { .. }
+        ));
+        assert_eq!((err.line, err.column), (5, 3));
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let err = parse_error(&format!("{}f 1 2 5\n", POSITIONS));
+        match err.kind {
+            ObjErrorKind::IndexOutOfRange {
+                element,
+                index,
+                count,
+            } => assert_eq!((element, index, count), ("position", 5, 4)),
+            kind => panic!("unexpected error: {}", kind),
+        }
+        assert_eq!((err.line, err.column), (5, 7));
+
+        let err = parse_error(&format!("{}f -5 1 2\n", POSITIONS));
+        assert!(matches!(err.kind, ObjErrorKind::IndexOutOfRange { .. }));
+        // texture coordinates are counted on their own
+        let err = parse_error(&format!("{}vt 0 0\nf 1/1 2/2 3/1\n", POSITIONS));
+        match err.kind {
+            ObjErrorKind::IndexOutOfRange { element, .. } => {
+                assert_eq!(element, "texture coordinate")
+            }
+            kind => panic!("unexpected error: {}", kind),
+        }
+    }
+
+    #[test]
+    fn floats_like_the_standard_library() {
+        let words = [
+            "0",
+            "-0",
+            "1",
+            "-1.5",
+            "+2.25",
+            ".5",
+            "-.5",
+            "+.5",
+            "5.",
+            "0.1",
+            "3.14159",
+            "1e10",
+            "1E-10",
+            "1e+3",
+            "-2.5e-3",
+            "6.02214076e23",
+            "1e38",
+            "3.4028235e38",
+            "1e39",
+            "-1e39",
+            "1e-38",
+            "1e-45",
+            "1e-46",
+            "1e-400",
+            "1e400",
+            "0.000001234",
+            "123456789012345",
+            "1234567890123456789",
+            "0.1234567890123456789",
+            "3.141592653589793238462643383279",
+            "1.00000005960464477539062500001",
+            "16777217",
+            "nan",
+            "NaN",
+            "-nan",
+            "inf",
+            "-inf",
+            "infinity",
+            "Infinity",
+        ];
+        for word in words.iter() {
+            let expected = f32::from_str(word).ok();
+            let parsed = parse_f32(word.as_bytes());
+            match (parsed, expected) {
+                (Some(parsed), Some(expected)) if expected.is_nan() => {
+                    assert!(parsed.is_nan(), "{}", word)
+                }
+                (Some(parsed), Some(expected)) => {
+                    assert_eq!(parsed.to_bits(), expected.to_bits(), "{}", word)
+                }
+                (parsed, expected) => assert_eq!(parsed, expected, "{}", word),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_floats() {
+        for word in [
+            "", "-", "+", ".", "e5", "1e", "1e+", "1.2.3", "1x", "--1", "0x10",
+        ]
+        .iter()
+        {
+            assert_eq!(parse_f32(word.as_bytes()), None, "{}", word);
+            assert!(f32::from_str(word).is_err(), "{}", word);
+        }
+    }
+
+    #[test]
+    fn float_sweep() {
+        // decimal numbers with up to 9 digits and all kinds of exponents, correctly rounded like the standard library
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        for _ in 0..200_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let mantissa = seed % 1_000_000_000;
+            let exponent = (seed >> 32) % 90;
+            let word = format!("{}e{}", mantissa, exponent as i64 - 50);
+            let expected = f32::from_str(&word).unwrap();
+            assert_eq!(
+                parse_f32(word.as_bytes()).map(f32::to_bits),
+                Some(expected.to_bits()),
+                "{}",
+                word
+            );
+        }
+    }
+}
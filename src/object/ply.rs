@@ -0,0 +1,610 @@
+//! Loader for PLY files, the format written by most 3D scanners, in ASCII or binary (little and big endian).
+//! http://paulbourke.net/dataformats/ply/
+//! Vertices get their position, normal, texture coordinates and color from the properties with the usual names,
+//! other properties and elements are read and ignored. Faces can have any number of vertices and get triangulated,
+//! their `texcoord` list (one u, v pair per vertex) is used when the vertices have no texture coordinates.
+//! A file without faces is a point cloud: each vertex becomes a point of the mesh.
+
+use super::error::{ObjError, ObjErrorKind};
+use super::mesh::{Corner, Mesh};
+use super::parser::{self, ObjData};
+use super::triangulate;
+use super::{Primitive, Submesh};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+impl Scalar {
+    fn from_name(name: &str) -> Option<Scalar> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        self == Scalar::F32 || self == Scalar::F64
+    }
+
+    /// Value that stands for 1 when the type is used for a color: integers span their whole positive range.
+    fn color_scale(self) -> f32 {
+        match self {
+            Scalar::I8 => 127.0,
+            Scalar::U8 => 255.0,
+            Scalar::I16 => 32767.0,
+            Scalar::U16 => 65535.0,
+            Scalar::I32 => 2_147_483_647.0,
+            Scalar::U32 => 4_294_967_295.0,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+enum PropertyType {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyType,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Where the value of a vertex property goes.
+#[derive(Clone, Copy)]
+enum Slot {
+    Position(usize),
+    Normal(usize),
+    Uv(usize),
+    Color(usize, f32),
+}
+impl Slot {
+    fn of(name: &str, scalar: Scalar) -> Option<Slot> {
+        Some(match name {
+            "x" => Slot::Position(0),
+            "y" => Slot::Position(1),
+            "z" => Slot::Position(2),
+            "nx" => Slot::Normal(0),
+            "ny" => Slot::Normal(1),
+            "nz" => Slot::Normal(2),
+            "u" | "s" | "texture_u" | "texture_s" => Slot::Uv(0),
+            "v" | "t" | "texture_v" | "texture_t" => Slot::Uv(1),
+            "red" | "diffuse_red" => Slot::Color(0, scalar.color_scale()),
+            "green" | "diffuse_green" => Slot::Color(1, scalar.color_scale()),
+            "blue" | "diffuse_blue" => Slot::Color(2, scalar.color_scale()),
+            _ => return None,
+        })
+    }
+}
+
+/// The vertex indices of the faces, resolved once all the elements are read since faces may come first.
+#[derive(Default)]
+struct Faces {
+    indices: Vec<i64>,
+    uvs: Vec<[f32; 2]>,
+    /// Number of vertices of each face, and the line where it is (0 in binary files).
+    faces: Vec<(usize, usize)>,
+}
+
+/// Load a .ply file whose content is `data`, `path` is used for errors.
+pub fn load(data: &[u8], path: &str) -> Result<ObjData, ObjError> {
+    let (format, elements, body, body_line) = read_header(data, path)?;
+    let mut reader = Reader {
+        path,
+        data: body,
+        at: 0,
+        format,
+        line: body_line,
+        line_start: 0,
+    };
+
+    let mut mesh = Mesh::default();
+    let mut has_normals = false;
+    let mut has_uvs = false;
+    let mut has_colors = false;
+    let mut faces = Faces::default();
+    for element in elements.iter() {
+        match element.name.as_str() {
+            "vertex" => {
+                let slots: Vec<Option<Slot>> = element
+                    .properties
+                    .iter()
+                    .map(|property| match property.kind {
+                        PropertyType::Scalar(scalar) => Slot::of(&property.name, scalar),
+                        PropertyType::List { .. } => None,
+                    })
+                    .collect();
+                let has = |wanted: fn(&Slot) -> bool| slots.iter().flatten().any(wanted);
+                if !has(|s| matches!(s, Slot::Position(_))) {
+                    return Err(reader.error(ObjErrorKind::BadValue(String::from(
+                        "the vertices have no x, y, z properties",
+                    ))));
+                }
+                has_normals = has(|s| matches!(s, Slot::Normal(_)));
+                has_uvs = has(|s| matches!(s, Slot::Uv(_)));
+                has_colors = has(|s| matches!(s, Slot::Color(..)));
+                read_vertices(&mut reader, element, &slots, &mut mesh)?;
+            }
+            "face" => read_faces(&mut reader, element, &mut faces)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in element.properties.iter() {
+                        reader.property(property, element, |_| {})?;
+                    }
+                }
+            }
+        }
+    }
+    if !has_normals {
+        mesh.normals.clear();
+    }
+    if !has_uvs {
+        mesh.uvs.clear();
+    }
+    if !has_colors {
+        mesh.colors.clear();
+    }
+
+    let count = mesh.positions.len();
+    let corner = |i: usize| Corner {
+        position: i as u32,
+        uv: if has_uvs { Some(i as u32) } else { None },
+        normal: if has_normals { Some(i as u32) } else { None },
+    };
+    let primitive = if faces.faces.is_empty() {
+        mesh.points = (0..count).map(corner).collect();
+        Primitive::Points
+    } else {
+        add_faces(&mut mesh, &faces, has_uvs, &corner, path)?;
+        Primitive::Triangles
+    };
+
+    let count = match primitive {
+        Primitive::Triangles => mesh.corners.len(),
+        Primitive::Points => mesh.points.len(),
+    };
+    Ok(ObjData {
+        mesh,
+        materials: Vec::new(),
+        submeshes: vec![Submesh {
+            object: None,
+            group: None,
+            material: None,
+            primitive,
+            start: 0,
+            count,
+            visible: true,
+        }],
+    })
+}
+
+/// The format, the elements, the data after the header and the number of its first line.
+fn read_header<'a>(
+    data: &'a [u8],
+    path: &str,
+) -> Result<(Format, Vec<Element>, &'a [u8], usize), ObjError> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut at = 0;
+    let mut number = 0;
+    loop {
+        number += 1;
+        let end = match data[at..].iter().position(|&b| b == b'\n') {
+            Some(end) => at + end,
+            None => {
+                return Err(ObjError::new(
+                    path,
+                    number,
+                    0,
+                    ObjErrorKind::BadValue(String::from("the header has no end_header")),
+                ))
+            }
+        };
+        let text = String::from_utf8_lossy(&data[at..end]);
+        at = end + 1;
+        let words: Vec<&str> = text.split_ascii_whitespace().collect();
+        let error = |index: usize, message: String| {
+            let column = words.get(index).map_or(0, |word| {
+                word.as_ptr() as usize - text.as_ptr() as usize + 1
+            });
+            ObjError::new(path, number, column, ObjErrorKind::BadValue(message))
+        };
+
+        if number == 1 {
+            if words != ["ply"] {
+                return Err(error(0, String::from("not a PLY file")));
+            }
+            continue;
+        }
+        match words.first().copied() {
+            Some("format") => {
+                format = Some(match words.get(1).copied() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(error(1, String::from("unknown format"))),
+                })
+            }
+            Some("element") => {
+                let count = words.get(2).and_then(|count| count.parse().ok());
+                match (words.get(1), count) {
+                    (Some(name), Some(count)) => elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    }),
+                    _ => return Err(error(2, String::from("expected 'element <name> <count>'"))),
+                }
+            }
+            Some("property") => {
+                let element = match elements.last_mut() {
+                    Some(element) => element,
+                    None => return Err(error(0, String::from("property before any element"))),
+                };
+                let scalar = |index: usize| {
+                    words
+                        .get(index)
+                        .and_then(|name| Scalar::from_name(name))
+                        .ok_or_else(|| error(index, String::from("unknown property type")))
+                };
+                let (kind, name) = if words.get(1) == Some(&"list") {
+                    let kind = PropertyType::List {
+                        count: scalar(2)?,
+                        item: scalar(3)?,
+                    };
+                    (kind, words.get(4))
+                } else {
+                    (PropertyType::Scalar(scalar(1)?), words.get(2))
+                };
+                match (name, kind) {
+                    (Some(name), kind) => element.properties.push(Property {
+                        name: name.to_string(),
+                        kind,
+                    }),
+                    (None, PropertyType::List { .. }) => {
+                        return Err(error(4, String::from("the property has no name")))
+                    }
+                    (None, PropertyType::Scalar(_)) => {
+                        return Err(error(2, String::from("the property has no name")))
+                    }
+                }
+            }
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            Some(_) => return Err(error(0, String::from("unknown header keyword"))),
+        }
+    }
+    match format {
+        Some(format) => Ok((format, elements, &data[at..], number + 1)),
+        None => Err(ObjError::new(
+            path,
+            number,
+            0,
+            ObjErrorKind::BadValue(String::from("the header has no format")),
+        )),
+    }
+}
+
+fn read_vertices(
+    reader: &mut Reader,
+    element: &Element,
+    slots: &[Option<Slot>],
+    mesh: &mut Mesh,
+) -> Result<(), ObjError> {
+    let rows = reader.rows_left(element);
+    mesh.positions.reserve(rows);
+    mesh.normals.reserve(rows);
+    mesh.uvs.reserve(rows);
+    mesh.colors.reserve(rows);
+    for _ in 0..element.count {
+        let mut position = [0.0; 3];
+        let mut normal = [0.0; 3];
+        let mut uv = [0.0; 2];
+        let mut color = [1.0; 3];
+        for (property, slot) in element.properties.iter().zip(slots) {
+            reader.property(property, element, |value| match *slot {
+                Some(Slot::Position(i)) => position[i] = value as f32,
+                Some(Slot::Normal(i)) => normal[i] = value as f32,
+                Some(Slot::Uv(i)) => uv[i] = value as f32,
+                Some(Slot::Color(i, scale)) => color[i] = value as f32 / scale,
+                None => {}
+            })?;
+        }
+        mesh.positions.push(position);
+        mesh.normals.push(normal);
+        mesh.uvs.push(uv);
+        mesh.colors.push(color);
+    }
+    Ok(())
+}
+
+fn read_faces(reader: &mut Reader, element: &Element, faces: &mut Faces) -> Result<(), ObjError> {
+    let is_list = |property: &&Property, names: &[&str]| {
+        matches!(property.kind, PropertyType::List { .. })
+            && names.contains(&property.name.as_str())
+    };
+    let indices = element
+        .properties
+        .iter()
+        .position(|p| is_list(&p, &["vertex_indices", "vertex_index"]));
+    let uvs = element
+        .properties
+        .iter()
+        .position(|p| is_list(&p, &["texcoord"]));
+    if indices.is_none() {
+        return Err(reader.error(ObjErrorKind::BadValue(String::from(
+            "the faces have no vertex_indices property",
+        ))));
+    }
+
+    let rows = reader.rows_left(element);
+    faces.faces.reserve(rows);
+    faces.indices.reserve(rows.checked_mul(3).unwrap_or(rows));
+    for _ in 0..element.count {
+        let line = reader.line_of_row();
+        let first = faces.indices.len();
+        let mut texcoords: Vec<f32> = Vec::new();
+        for (i, property) in element.properties.iter().enumerate() {
+            if Some(i) == indices {
+                reader.property(property, element, |value| faces.indices.push(value as i64))?;
+            } else if Some(i) == uvs {
+                reader.property(property, element, |value| texcoords.push(value as f32))?;
+            } else {
+                reader.property(property, element, |_| {})?;
+            }
+        }
+        let arity = faces.indices.len() - first;
+        if texcoords.len() == 2 * arity {
+            faces
+                .uvs
+                .extend(texcoords.chunks_exact(2).map(|t| [t[0], t[1]]));
+        }
+        faces.faces.push((arity, line));
+    }
+    if faces.uvs.len() != faces.indices.len() {
+        // only some faces have texture coordinates, ignore them all
+        faces.uvs.clear();
+    }
+    Ok(())
+}
+
+/// Triangulate the faces into the mesh, checking their indices.
+fn add_faces(
+    mesh: &mut Mesh,
+    faces: &Faces,
+    has_uvs: bool,
+    corner: &dyn Fn(usize) -> Corner,
+    path: &str,
+) -> Result<(), ObjError> {
+    let face_uvs = !has_uvs && !faces.uvs.is_empty();
+    if face_uvs {
+        mesh.uvs = faces.uvs.clone();
+    }
+
+    let mut first = 0;
+    let mut corners: Vec<Corner> = Vec::new();
+    for &(arity, line) in faces.faces.iter() {
+        let indices = &faces.indices[first..first + arity];
+        if arity < 3 {
+            return Err(ObjError::new(
+                path,
+                line,
+                0,
+                ObjErrorKind::UnsupportedFaceArity(arity),
+            ));
+        }
+        corners.clear();
+        for (k, &index) in indices.iter().enumerate() {
+            if index < 0 || index as usize >= mesh.positions.len() {
+                return Err(ObjError::new(
+                    path,
+                    line,
+                    0,
+                    ObjErrorKind::IndexOutOfRange {
+                        element: "vertex",
+                        index,
+                        count: mesh.positions.len(),
+                    },
+                ));
+            }
+            let mut c = corner(index as usize);
+            if face_uvs {
+                c.uv = Some((first + k) as u32);
+            }
+            corners.push(c);
+        }
+
+        let triangles = mesh.triangles_count();
+        if arity == 3 {
+            mesh.push_triangle([corners[0], corners[1], corners[2]], 1);
+        } else {
+            let points: Vec<[f32; 3]> = corners
+                .iter()
+                .map(|c| mesh.positions[c.position as usize])
+                .collect();
+            for (a, b, c) in triangulate::triangulate(&points) {
+                mesh.push_triangle([corners[a], corners[b], corners[c]], 1);
+            }
+        }
+        if mesh.triangles_count() > triangles {
+            mesh.polygons.push((mesh.triangles_count() - triangles) as u32);
+        }
+        first += arity;
+    }
+    Ok(())
+}
+
+/// Reads the values of the elements after the header.
+struct Reader<'a> {
+    path: &'a str,
+    data: &'a [u8],
+    at: usize,
+    format: Format,
+    /// line and start of the line of the next value in ASCII files
+    line: usize,
+    line_start: usize,
+}
+impl<'a> Reader<'a> {
+    fn error(&self, kind: ObjErrorKind) -> ObjError {
+        match self.format {
+            Format::Ascii => ObjError::new(self.path, self.line, 0, kind),
+            _ => ObjError::new(self.path, 0, 0, kind),
+        }
+    }
+
+    /// How many rows of `element` can still be in the file: its count, unless the header promises more rows than
+    /// there are bytes left, so that a broken count doesn't reserve more memory than the file could ever fill.
+    fn rows_left(&self, element: &Element) -> usize {
+        element.count.min(self.data.len().saturating_sub(self.at))
+    }
+
+    /// Line where the next row of an element starts, 0 in binary files.
+    fn line_of_row(&mut self) -> usize {
+        if self.format == Format::Ascii {
+            self.skip_whitespace();
+            self.line
+        } else {
+            0
+        }
+    }
+
+    /// Read a property, calling `value` for a scalar or for each item of a list.
+    fn property(
+        &mut self,
+        property: &Property,
+        element: &Element,
+        mut value: impl FnMut(f64),
+    ) -> Result<(), ObjError> {
+        match property.kind {
+            PropertyType::Scalar(scalar) => value(self.value(scalar, element)?),
+            PropertyType::List { count, item } => {
+                let n = self.value(count, element)?;
+                if n < 0.0 || n.fract() != 0.0 {
+                    return Err(self.error(ObjErrorKind::BadValue(format!(
+                        "{} is not a valid length for the list '{}'",
+                        n, property.name
+                    ))));
+                }
+                for _ in 0..n as usize {
+                    value(self.value(item, element)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, scalar: Scalar, element: &Element) -> Result<f64, ObjError> {
+        if self.format == Format::Ascii {
+            return self.ascii_value(scalar, element);
+        }
+
+        let size = scalar.size();
+        if self.data.len() - self.at < size {
+            return Err(self.error(ObjErrorKind::BadValue(format!(
+                "the file ends in the middle of the '{}' elements",
+                element.name
+            ))));
+        }
+        let mut bytes = [0; 8];
+        bytes[..size].copy_from_slice(&self.data[self.at..self.at + size]);
+        self.at += size;
+        if self.format == Format::BinaryBigEndian {
+            bytes[..size].reverse();
+        }
+        Ok(match scalar {
+            Scalar::I8 => bytes[0] as i8 as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes),
+        })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(&b) = self.data.get(self.at) {
+            if b == b'\n' {
+                self.line += 1;
+                self.line_start = self.at + 1;
+            } else if !b.is_ascii_whitespace() {
+                break;
+            }
+            self.at += 1;
+        }
+    }
+
+    fn ascii_value(&mut self, scalar: Scalar, element: &Element) -> Result<f64, ObjError> {
+        self.skip_whitespace();
+        let start = self.at;
+        while self
+            .data
+            .get(self.at)
+            .map_or(false, |b| !b.is_ascii_whitespace())
+        {
+            self.at += 1;
+        }
+        let word = &self.data[start..self.at];
+        if word.is_empty() {
+            return Err(self.error(ObjErrorKind::BadValue(format!(
+                "the file ends in the middle of the '{}' elements",
+                element.name
+            ))));
+        }
+
+        let value = if scalar.is_float() {
+            parser::parse_f32(word).map(|value| value as f64)
+        } else {
+            std::str::from_utf8(word)
+                .ok()
+                .and_then(|word| word.parse::<i64>().ok())
+                .map(|value| value as f64)
+        };
+        value.ok_or_else(|| {
+            ObjError::new(
+                self.path,
+                self.line,
+                start - self.line_start + 1,
+                ObjErrorKind::BadFloat(String::from_utf8_lossy(word).into_owned()),
+            )
+        })
+    }
+}
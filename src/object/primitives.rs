@@ -0,0 +1,280 @@
+//! Meshes generated instead of loaded, to preview materials on the usual shapes.
+//! They have texture coordinates, normals and polygons like a loaded .obj file: obj builds their tangents and they
+//! can be subdivided. Every shape is about 2 units wide, centered on the origin, with Y up.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use super::math;
+use super::mesh::{Corner, Mesh};
+use super::parser::ObjData;
+use super::{Primitive, Submesh};
+
+/// Segments around the round shapes.
+pub const SEGMENTS: usize = 48;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Sphere,
+    Cube,
+    Plane,
+    Cylinder,
+    Torus,
+    /// A ball sunk in a pedestal with a ring around it, showing the material on convex, concave and flat
+    /// surfaces and where they meet.
+    ShaderBall,
+}
+impl Shape {
+    pub const ALL: [Shape; 6] = [
+        Shape::Sphere,
+        Shape::Cube,
+        Shape::Plane,
+        Shape::Cylinder,
+        Shape::Torus,
+        Shape::ShaderBall,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Shape::Sphere => "sphere",
+            Shape::Cube => "cube",
+            Shape::Plane => "plane",
+            Shape::Cylinder => "cylinder",
+            Shape::Torus => "torus",
+            Shape::ShaderBall => "shader_ball",
+        }
+    }
+}
+
+/// The mesh of `shape`, with one submesh named after each of its parts and no material.
+/// Flat faces have smoothing groups of their own, so that their edges stay sharp when the shape is subdivided.
+pub fn generate(shape: Shape) -> ObjData {
+    let mut builder = Builder::default();
+    match shape {
+        Shape::Sphere => {
+            builder.part(shape.name());
+            sphere(&mut builder, [0.0; 3], 1.0);
+        }
+        Shape::Cube => {
+            builder.part(shape.name());
+            cube(&mut builder);
+        }
+        Shape::Plane => {
+            builder.part(shape.name());
+            let corner = [-1.0, 0.0, 1.0];
+            builder.grid(10, 10, 1, &|u, v| {
+                let p = [corner[0] + 2.0 * u, 0.0, corner[2] - 2.0 * v];
+                (p, [0.0, 1.0, 0.0], [u, v])
+            });
+        }
+        Shape::Cylinder => {
+            builder.part(shape.name());
+            cylinder(&mut builder, [0.0; 3], 1.0, 2.0);
+        }
+        Shape::Torus => {
+            builder.part(shape.name());
+            torus(&mut builder, [0.0; 3], 0.75, 0.25);
+        }
+        Shape::ShaderBall => {
+            builder.part("pedestal");
+            cylinder(&mut builder, [0.0, -0.85, 0.0], 0.8, 0.2);
+            builder.part("ball");
+            sphere(&mut builder, [0.0, 0.0, 0.0], 0.8);
+            builder.part("ring");
+            torus(&mut builder, [0.0; 3], 0.85, 0.08);
+        }
+    }
+    builder.finish()
+}
+
+/// Direction around the vertical axis at `u` (from 0 to 1) with the seam at the back, turning so that textures
+/// read from left to right seen from the front.
+fn around(u: f32) -> [f32; 3] {
+    // the last column is exactly the first one again, to share its positions
+    let angle = 2.0 * PI * if u < 1.0 { u } else { 0.0 };
+    [-angle.sin(), 0.0, -angle.cos()]
+}
+
+fn sphere(builder: &mut Builder, center: [f32; 3], radius: f32) {
+    builder.grid(SEGMENTS, SEGMENTS / 2, 1, &|u, v| {
+        // v goes from the bottom pole to the top one, which are single points
+        let latitude = PI * (v - 0.5);
+        let ring = if v > 0.0 && v < 1.0 {
+            latitude.cos()
+        } else {
+            0.0
+        };
+        let n = math::add(math::scale(around(u), ring), [0.0, latitude.sin(), 0.0]);
+        (math::add(center, math::scale(n, radius)), n, [u, v])
+    });
+}
+
+fn cube(builder: &mut Builder) {
+    // normal and the directions of u and v of each face, seen from the outside
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    for (group, &(n, tangent, bitangent)) in faces.iter().enumerate() {
+        builder.grid(1, 1, group as u32 + 1, &|u, v| {
+            let p = math::add(
+                n,
+                math::add(
+                    math::scale(tangent, 2.0 * u - 1.0),
+                    math::scale(bitangent, 2.0 * v - 1.0),
+                ),
+            );
+            (p, n, [u, v])
+        });
+    }
+}
+
+/// A cylinder standing on the XZ plane through `center`, closed by two discs.
+fn cylinder(builder: &mut Builder, center: [f32; 3], radius: f32, height: f32) {
+    let top = center[1] + 0.5 * height;
+    let bottom = center[1] - 0.5 * height;
+    builder.grid(SEGMENTS, 1, 1, &|u, v| {
+        let n = around(u);
+        let p = math::add(center, math::scale(n, radius));
+        // the same heights as the discs at the ends
+        let y = if v < 1.0 { bottom + height * v } else { top };
+        ([p[0], y, p[2]], n, [u, v])
+    });
+    disc(builder, [center[0], top, center[2]], radius, true);
+    disc(builder, [center[0], bottom, center[2]], radius, false);
+}
+
+/// A horizontal disc facing up or down, whose texture coordinates are a view from above.
+fn disc(builder: &mut Builder, center: [f32; 3], radius: f32, up: bool) {
+    let (normal, group) = if up {
+        ([0.0, 1.0, 0.0], 2)
+    } else {
+        ([0.0, -1.0, 0.0], 3)
+    };
+    builder.grid(SEGMENTS, 1, group, &|u, v| {
+        // from the rim to the center facing up, and the other way facing down
+        let distance = if up { 1.0 - v } else { v };
+        let p = math::add(center, math::scale(around(u), radius * distance));
+        let uv = [
+            0.5 + (p[0] - center[0]) / (2.0 * radius),
+            0.5 - (p[2] - center[2]) / (2.0 * radius),
+        ];
+        (p, normal, uv)
+    });
+}
+
+/// A torus around the vertical axis through `center`: `major` is the radius of the circle at the center of the
+/// tube, `minor` the radius of the tube. v goes around the tube from its inside.
+fn torus(builder: &mut Builder, center: [f32; 3], major: f32, minor: f32) {
+    builder.grid(SEGMENTS, SEGMENTS / 2, 1, &|u, v| {
+        let out = around(u);
+        let angle = 2.0 * PI * if v < 1.0 { v } else { 0.0 } - PI;
+        let n = math::add(math::scale(out, angle.cos()), [0.0, angle.sin(), 0.0]);
+        let p = math::add(
+            center,
+            math::add(math::scale(out, major), math::scale(n, minor)),
+        );
+        (p, n, [u, v])
+    });
+}
+
+/// Position, normal and texture coordinates of a point of a surface, from its parameters u and v going from 0 to 1.
+type Surface<'a> = &'a dyn Fn(f32, f32) -> ([f32; 3], [f32; 3], [f32; 2]);
+
+#[derive(Default)]
+struct Builder {
+    mesh: Mesh,
+    submeshes: Vec<Submesh>,
+    /// Index of each position, by its value.
+    positions: HashMap<[u32; 3], u32>,
+}
+impl Builder {
+    /// Start a submesh named `name`, the next triangles go into it.
+    fn part(&mut self, name: &str) {
+        self.submeshes.push(Submesh {
+            object: Some(String::from(name)),
+            group: None,
+            material: None,
+            primitive: Primitive::Triangles,
+            start: self.mesh.corners.len(),
+            count: 0,
+            visible: true,
+        });
+    }
+
+    /// A grid of quads on `surface`, facing the side towards which the cross product of the directions of u and v
+    /// points. Positions are shared with the rest of the mesh, so that surfaces closed by their seams and the parts
+    /// meeting at an edge are connected. Triangles without area, at poles and centers, are left out.
+    fn grid(&mut self, columns: usize, rows: usize, smoothing_group: u32, surface: Surface) {
+        let first = self.mesh.uvs.len() as u32;
+        let mut positions = Vec::with_capacity((columns + 1) * (rows + 1));
+        for j in 0..=rows {
+            for i in 0..=columns {
+                let (p, n, uv) = surface(i as f32 / columns as f32, j as f32 / rows as f32);
+                let key = [
+                    (p[0] + 0.0).to_bits(),
+                    (p[1] + 0.0).to_bits(),
+                    (p[2] + 0.0).to_bits(),
+                ];
+                let next = self.positions.len() as u32;
+                let position = *self.positions.entry(key).or_insert(next);
+                if position == next {
+                    self.mesh.positions.push(p);
+                }
+                positions.push(position);
+                self.mesh.normals.push(n);
+                self.mesh.uvs.push(uv);
+            }
+        }
+
+        let corner = |i: usize, j: usize| {
+            let node = j * (columns + 1) + i;
+            Corner {
+                position: positions[node],
+                uv: Some(first + node as u32),
+                normal: Some(first + node as u32),
+            }
+        };
+        for j in 0..rows {
+            for i in 0..columns {
+                let quad = [
+                    corner(i, j),
+                    corner(i + 1, j),
+                    corner(i + 1, j + 1),
+                    corner(i, j + 1),
+                ];
+                let mut triangles = 0;
+                for &[a, b, c] in [[0, 1, 2], [0, 2, 3]].iter() {
+                    let triangle = [quad[a], quad[b], quad[c]];
+                    let position = |k: usize| self.mesh.positions[triangle[k].position as usize];
+                    let normal = math::cross(
+                        math::sub(position(1), position(0)),
+                        math::sub(position(2), position(0)),
+                    );
+                    if math::length(normal) > 0.0 {
+                        self.mesh.push_triangle(triangle, smoothing_group);
+                        triangles += 1;
+                    }
+                }
+                if triangles > 0 {
+                    self.mesh.polygons.push(triangles);
+                }
+            }
+        }
+        if let Some(submesh) = self.submeshes.last_mut() {
+            submesh.count = self.mesh.corners.len() - submesh.start;
+        }
+    }
+
+    fn finish(self) -> ObjData {
+        ObjData {
+            mesh: self.mesh,
+            materials: Vec::new(),
+            submeshes: self.submeshes,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Access to the files a model references (.mtl libraries, glTF buffers, textures), so that models can be
+//! loaded from memory, archives or stdin as well as from the filesystem.
+//! Names are given as written in the model, with '/' separators and relative to the model.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub trait Resolver {
+    /// Content of the file referenced as `name`.
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+
+    /// Where `name` is on disk, None when the files are not on disk.
+    /// Textures with a path are loaded later by the renderer, the others are read with `read` when the model is loaded.
+    fn path(&self, _name: &str) -> Option<PathBuf> {
+        None
+    }
+
+    /// How `name` is shown in messages.
+    fn describe(&self, name: &str) -> String {
+        self.path(name).map_or_else(
+            || String::from(name),
+            |path| path.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+/// Files relative to a folder, the one of the model for models loaded from a path.
+pub struct FileResolver {
+    pub folder: PathBuf,
+}
+impl FileResolver {
+    /// Resolves the files referenced by the model at `path`.
+    pub fn next_to(path: &str) -> FileResolver {
+        FileResolver {
+            folder: Path::new(path)
+                .parent()
+                .unwrap_or_else(|| Path::new(""))
+                .to_path_buf(),
+        }
+    }
+}
+impl Resolver for FileResolver {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        std::fs::read(self.folder.join(name))
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        Some(self.folder.join(name))
+    }
+}
+
+/// Any closure reading a name can be a resolver, e.g. looking up the entries of an archive or
+/// always failing for models without any other file.
+impl<F: Fn(&str) -> io::Result<Vec<u8>>> Resolver for F {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self(name)
+    }
+}
+
+/// Name of a file referenced from the file `base`, both relative to the model. Absolute names are kept as they are.
+pub fn relative_to(base: &str, name: &str) -> String {
+    match base.rfind('/') {
+        Some(slash) if !Path::new(name).is_absolute() => format!("{}/{}", &base[..slash], name),
+        _ => String::from(name),
+    }
+}
+
+/// The path and the encoded image of a texture: only the path when it is on disk, the content read through
+/// `resolver` otherwise. A texture that can't be read is only reported, materials are still worth showing without it.
+pub fn texture(resolver: &dyn Resolver, name: &str) -> (PathBuf, Option<Arc<Vec<u8>>>) {
+    if let Some(path) = resolver.path(name) {
+        return (path, None);
+    }
+    match resolver.read(name) {
+        Ok(data) => (PathBuf::from(name), Some(Arc::new(data))),
+        Err(err) => {
+            println!("WARN: could not read texture '{}': {}", name, err);
+            (PathBuf::from(name), None)
+        }
+    }
+}
@@ -0,0 +1,616 @@
+//! Simplification of the indexed mesh by edge collapses in the order of their quadric error
+//! (Garland and Heckbert 1997), and chains of levels of detail to draw small models with fewer triangles.
+//! Vertices are only moved onto one of their neighbours, so the levels share the vertices of the model and only the
+//! indices change. Vertices at the same position with different attributes (UV and normal seams) collapse along the
+//! seam together, and so do the borders of open surfaces and between submeshes: seams and borders stay in place.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::bounds::Bounds;
+use super::math;
+use super::Primitive;
+
+/// Triangles of each level of a chain, relative to the previous level.
+pub const LOD_RATIO: f32 = 0.5;
+/// A chain stops before the levels get fewer triangles than this.
+pub const LOD_MIN_TRIANGLES: usize = 256;
+/// A chain stops before the surface moves further than this, relative to the diagonal of the model bounds.
+pub const LOD_MAX_ERROR: f32 = 0.05;
+/// Weight of the planes keeping borders and seams in place, relative to the planes of the faces.
+const BORDER_WEIGHT: f64 = 10.0;
+/// A collapse can't turn a triangle by more than acos of this.
+const MIN_NORMAL_COS: f32 = 0.2;
+
+/// Indices of a level of detail, drawn with the vertices of the model.
+#[derive(Clone, Debug)]
+pub struct Lod {
+    /// three indices per triangle, one per point
+    pub indices: Vec<u32>,
+    /// start and count of each submesh of the model in `indices`
+    pub ranges: Vec<(usize, usize)>,
+    pub triangles: usize,
+    /// how far the surface may be from the full model, relative to the diagonal of its bounds
+    pub error: f32,
+}
+
+/// Collapse edges of `source` until it has `target_triangles` or until the next collapse would move the surface
+/// further than `target_error`, relative to the diagonal of the model bounds.
+/// `vertices` are the vertices of the model with `stride` floats each, starting with the position, and
+/// `primitives` are those of its submeshes, points are kept as they are.
+pub fn simplify(
+    vertices: &[f32],
+    stride: usize,
+    source: &Lod,
+    primitives: &[Primitive],
+    target_triangles: usize,
+    target_error: f32,
+) -> Lod {
+    let mut simplifier = Simplifier::new(vertices, stride, source, primitives);
+    let max_cost = (target_error as f64 * simplifier.diagonal as f64).powi(2);
+    let error = simplifier.run(target_triangles, max_cost);
+
+    let mut lod = Lod {
+        indices: Vec::with_capacity(3 * simplifier.triangles_left),
+        ranges: Vec::with_capacity(source.ranges.len()),
+        triangles: simplifier.triangles_left,
+        error: source.error + error,
+    };
+    let mut by_submesh: Vec<Vec<usize>> = vec![Vec::new(); source.ranges.len()];
+    for (t, &submesh) in simplifier.triangle_submesh.iter().enumerate() {
+        if simplifier.alive[t] {
+            by_submesh[submesh].push(t);
+        }
+    }
+    for (submesh, &(start, count)) in source.ranges.iter().enumerate() {
+        let first = lod.indices.len();
+        match primitives[submesh] {
+            Primitive::Triangles => {
+                for &t in by_submesh[submesh].iter() {
+                    lod.indices.extend_from_slice(&simplifier.triangles[t]);
+                }
+            }
+            Primitive::Points => {
+                lod.indices
+                    .extend_from_slice(&source.indices[start..start + count]);
+            }
+        }
+        lod.ranges.push((first, lod.indices.len() - first));
+    }
+    lod
+}
+
+/// Levels of detail simplified from `base`, each with LOD_RATIO times the triangles of the previous one,
+/// until they get too small, too far from `base` or can't be simplified further. `base` itself is not included.
+pub fn lod_chain(
+    vertices: &[f32],
+    stride: usize,
+    base: &Lod,
+    primitives: &[Primitive],
+) -> Vec<Lod> {
+    let mut chain: Vec<Lod> = Vec::new();
+    loop {
+        let previous = chain.last().unwrap_or(base);
+        let target = (previous.triangles as f32 * LOD_RATIO) as usize;
+        if target < LOD_MIN_TRIANGLES {
+            break;
+        }
+        let lod = simplify(
+            vertices,
+            stride,
+            previous,
+            primitives,
+            target,
+            LOD_MAX_ERROR - previous.error,
+        );
+        // locked seams and borders or the error limit can leave too much to be worth a level
+        if lod.triangles as f32 > previous.triangles as f32 * (1.0 + LOD_RATIO) / 2.0 {
+            break;
+        }
+        chain.push(lod);
+    }
+    chain
+}
+
+/// Error quadric: the sum of the squared distances to weighted planes,
+/// as the upper half of a symmetric 4x4 matrix and the sum of the weights.
+#[derive(Clone, Copy, Default)]
+struct Quadric {
+    m: [f64; 10],
+    weight: f64,
+}
+impl Quadric {
+    /// The plane through `p` with the unit normal `n`.
+    fn plane(n: [f32; 3], p: [f32; 3], weight: f64) -> Quadric {
+        let [a, b, c] = [n[0] as f64, n[1] as f64, n[2] as f64];
+        let d = -(a * p[0] as f64 + b * p[1] as f64 + c * p[2] as f64);
+        let w = weight;
+        Quadric {
+            m: [
+                w * a * a,
+                w * a * b,
+                w * a * c,
+                w * a * d,
+                w * b * b,
+                w * b * c,
+                w * b * d,
+                w * c * c,
+                w * c * d,
+                w * d * d,
+            ],
+            weight,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (m, o) in self.m.iter_mut().zip(other.m.iter()) {
+            *m += o;
+        }
+        self.weight += other.weight;
+    }
+
+    /// Weighted mean of the squared distances from `p` to the planes.
+    fn error(&self, p: [f32; 3]) -> f64 {
+        let [x, y, z] = [p[0] as f64, p[1] as f64, p[2] as f64];
+        let m = &self.m;
+        let sum = m[0] * x * x
+            + 2.0 * m[1] * x * y
+            + 2.0 * m[2] * x * z
+            + 2.0 * m[3] * x
+            + m[4] * y * y
+            + 2.0 * m[5] * y * z
+            + 2.0 * m[6] * y
+            + m[7] * z * z
+            + 2.0 * m[8] * z
+            + m[9];
+        if self.weight > 0.0 {
+            sum.max(0.0) / self.weight
+        } else {
+            sum.max(0.0)
+        }
+    }
+}
+
+/// What a node can collapse along.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// any edge, the node has a single vertex
+    Interior,
+    /// one of its two open edges
+    Border,
+    /// one of its two seam edges, between vertices with different attributes or between submeshes
+    Seam,
+    /// nothing: corners, non-manifold edges and vertices on several seams
+    Locked,
+}
+
+/// A node collapse waiting in the queue, the cheapest first.
+struct Candidate {
+    cost: f64,
+    node: u32,
+    version: u32,
+}
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for Candidate {}
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .cost
+            .partial_cmp(&self.cost)
+            .unwrap_or(Ordering::Equal)
+            .then(other.node.cmp(&self.node))
+    }
+}
+
+/// A collapse of a node onto a neighbour, with the vertex replacing each vertex of the node.
+struct Collapse {
+    cost: f64,
+    target: u32,
+    remap: Vec<(u32, u32)>,
+}
+
+/// The triangles of a level as vertices (wedges) grouped in nodes, one node per distinct position.
+struct Simplifier {
+    /// position of each node
+    positions: Vec<[f32; 3]>,
+    triangles: Vec<[u32; 3]>,
+    /// node of each corner of the triangles
+    triangle_nodes: Vec<[u32; 3]>,
+    triangle_submesh: Vec<usize>,
+    alive: Vec<bool>,
+    triangles_left: usize,
+    /// triangles using each node, including dead ones until they are cleaned
+    node_triangles: Vec<Vec<u32>>,
+    quadrics: Vec<Quadric>,
+    removed: Vec<bool>,
+    version: Vec<u32>,
+    diagonal: f32,
+}
+impl Simplifier {
+    fn new(vertices: &[f32], stride: usize, source: &Lod, primitives: &[Primitive]) -> Simplifier {
+        let mut simplifier = Simplifier {
+            positions: Vec::new(),
+            triangles: Vec::new(),
+            triangle_nodes: Vec::new(),
+            triangle_submesh: Vec::new(),
+            alive: Vec::new(),
+            triangles_left: 0,
+            node_triangles: Vec::new(),
+            quadrics: Vec::new(),
+            removed: Vec::new(),
+            version: Vec::new(),
+            diagonal: 0.0,
+        };
+
+        let mut nodes: HashMap<[u32; 3], u32> = HashMap::new();
+        for (submesh, &(start, count)) in source.ranges.iter().enumerate() {
+            if primitives[submesh] != Primitive::Triangles {
+                continue;
+            }
+            for triangle in source.indices[start..start + count].chunks_exact(3) {
+                let mut corners = [0; 3];
+                for (k, &wedge) in triangle.iter().enumerate() {
+                    let offset = wedge as usize * stride;
+                    let p = [vertices[offset], vertices[offset + 1], vertices[offset + 2]];
+                    let positions = &mut simplifier.positions;
+                    let node = *nodes
+                        .entry([p[0].to_bits(), p[1].to_bits(), p[2].to_bits()])
+                        .or_insert_with(|| {
+                            positions.push(p);
+                            positions.len() as u32 - 1
+                        });
+                    corners[k] = node;
+                }
+                // triangles with welded corners have no area and no edges to collapse
+                if corners[0] == corners[1] || corners[1] == corners[2] || corners[2] == corners[0]
+                {
+                    continue;
+                }
+                simplifier
+                    .triangles
+                    .push([triangle[0], triangle[1], triangle[2]]);
+                simplifier.triangle_nodes.push(corners);
+                simplifier.triangle_submesh.push(submesh);
+            }
+        }
+
+        let nodes_count = simplifier.positions.len();
+        simplifier.alive = vec![true; simplifier.triangles.len()];
+        simplifier.triangles_left = simplifier.triangles.len();
+        simplifier.node_triangles = vec![Vec::new(); nodes_count];
+        simplifier.quadrics = vec![Quadric::default(); nodes_count];
+        simplifier.removed = vec![false; nodes_count];
+        simplifier.version = vec![0; nodes_count];
+        simplifier.diagonal = Bounds::new(simplifier.positions.iter().copied())
+            .map_or(0.0, |bounds| bounds.diagonal());
+
+        for t in 0..simplifier.triangles.len() {
+            let nodes = simplifier.triangle_nodes[t];
+            let p = simplifier.node_positions(nodes);
+            let normal = math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0]));
+            let area = math::length(normal) as f64 * 0.5;
+            let quadric = Quadric::plane(math::normalize(normal), p[0], area);
+            for &node in nodes.iter() {
+                simplifier.node_triangles[node as usize].push(t as u32);
+                simplifier.quadrics[node as usize].add(&quadric);
+            }
+        }
+        // planes across the open and seam edges, so that they don't move sideways
+        for t in 0..simplifier.triangles.len() {
+            let nodes = simplifier.triangle_nodes[t];
+            let p = simplifier.node_positions(nodes);
+            let normal = math::normalize(math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0])));
+            for k in 0..3 {
+                let (a, b) = (nodes[k], nodes[(k + 1) % 3]);
+                let shared = simplifier.edge_triangles(a, b);
+                if shared.len() == 2 && !simplifier.is_seam(a, b, &shared) {
+                    continue;
+                }
+                let edge = math::sub(p[(k + 1) % 3], p[k]);
+                let length = math::length(edge) as f64;
+                let across = math::normalize(math::cross(edge, normal));
+                let quadric = Quadric::plane(across, p[k], BORDER_WEIGHT * length * length);
+                simplifier.quadrics[a as usize].add(&quadric);
+                simplifier.quadrics[b as usize].add(&quadric);
+            }
+        }
+        simplifier
+    }
+
+    /// Collapse nodes until `target_triangles` are left or the cheapest collapse costs more than `max_cost`.
+    /// Returns the largest distance that a collapse moved the surface, relative to the diagonal.
+    fn run(&mut self, target_triangles: usize, max_cost: f64) -> f32 {
+        let mut queue = BinaryHeap::new();
+        for node in 0..self.positions.len() as u32 {
+            if let Some(collapse) = self.best_collapse(node) {
+                queue.push(Candidate {
+                    cost: collapse.cost,
+                    node,
+                    version: 0,
+                });
+            }
+        }
+
+        let mut largest_cost: f64 = 0.0;
+        while self.triangles_left > target_triangles {
+            let candidate = match queue.pop() {
+                Some(candidate) => candidate,
+                None => break,
+            };
+            let node = candidate.node as usize;
+            if self.removed[node] || self.version[node] != candidate.version {
+                continue;
+            }
+            if candidate.cost > max_cost {
+                break;
+            }
+            // the neighbourhood may have changed since the candidate was queued
+            let collapse = match self.best_collapse(candidate.node) {
+                Some(collapse) => collapse,
+                None => continue,
+            };
+            if collapse.cost > candidate.cost * (1.0 + 1e-6) + f64::EPSILON {
+                queue.push(Candidate {
+                    cost: collapse.cost,
+                    node: candidate.node,
+                    version: candidate.version,
+                });
+                continue;
+            }
+
+            largest_cost = largest_cost.max(collapse.cost);
+            let target = collapse.target;
+            self.collapse(candidate.node, collapse);
+            let mut affected = self.neighbours(target);
+            affected.push(target);
+            for node in affected {
+                self.version[node as usize] += 1;
+                if let Some(collapse) = self.best_collapse(node) {
+                    queue.push(Candidate {
+                        cost: collapse.cost,
+                        node,
+                        version: self.version[node as usize],
+                    });
+                }
+            }
+        }
+
+        if self.diagonal > 0.0 {
+            largest_cost.sqrt() as f32 / self.diagonal
+        } else {
+            0.0
+        }
+    }
+
+    fn node_positions(&self, nodes: [u32; 3]) -> [[f32; 3]; 3] {
+        let [a, b, c] = nodes;
+        [
+            self.positions[a as usize],
+            self.positions[b as usize],
+            self.positions[c as usize],
+        ]
+    }
+
+    fn live_triangles(&self, node: u32) -> impl Iterator<Item = usize> + '_ {
+        self.node_triangles[node as usize]
+            .iter()
+            .map(|&t| t as usize)
+            .filter(move |&t| self.alive[t])
+    }
+
+    fn edge_triangles(&self, a: u32, b: u32) -> Vec<usize> {
+        self.live_triangles(a)
+            .filter(|&t| self.triangle_nodes[t].contains(&b))
+            .collect()
+    }
+
+    fn neighbours(&self, node: u32) -> Vec<u32> {
+        let mut neighbours: Vec<u32> = self
+            .live_triangles(node)
+            .flat_map(|t| self.triangle_nodes[t])
+            .filter(|&n| n != node)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// Vertex used by triangle `t` at `node`.
+    fn wedge(&self, t: usize, node: u32) -> u32 {
+        let nodes = self.triangle_nodes[t];
+        let k = nodes.iter().position(|&n| n == node).unwrap();
+        self.triangles[t][k]
+    }
+
+    /// Whether the edge shared by the two triangles `shared` separates different vertices or submeshes.
+    fn is_seam(&self, a: u32, b: u32, shared: &[usize]) -> bool {
+        let (t0, t1) = (shared[0], shared[1]);
+        self.triangle_submesh[t0] != self.triangle_submesh[t1]
+            || self.wedge(t0, a) != self.wedge(t1, a)
+            || self.wedge(t0, b) != self.wedge(t1, b)
+    }
+
+    /// Neighbours of `node`, each with the triangles on the edge between them.
+    fn edges(&self, node: u32) -> Vec<(u32, Vec<usize>)> {
+        let mut ends: Vec<(u32, usize)> = self
+            .live_triangles(node)
+            .flat_map(|t| {
+                self.triangle_nodes[t]
+                    .iter()
+                    .filter(|&&n| n != node)
+                    .map(|&n| (n, t))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        ends.sort_unstable();
+        let mut edges: Vec<(u32, Vec<usize>)> = Vec::new();
+        for (neighbour, t) in ends {
+            match edges.last_mut() {
+                Some((last, triangles)) if *last == neighbour => triangles.push(t),
+                _ => edges.push((neighbour, vec![t])),
+            }
+        }
+        edges
+    }
+
+    fn kind(&self, node: u32, edges: &[(u32, Vec<usize>)]) -> Kind {
+        let mut wedges: Vec<u32> = self
+            .live_triangles(node)
+            .map(|t| self.wedge(t, node))
+            .collect();
+        wedges.sort_unstable();
+        wedges.dedup();
+
+        let mut open = 0;
+        let mut seams = 0;
+        for (neighbour, shared) in edges.iter() {
+            match shared.len() {
+                1 => open += 1,
+                2 => {
+                    if self.is_seam(node, *neighbour, shared) {
+                        seams += 1;
+                    }
+                }
+                _ => return Kind::Locked,
+            }
+        }
+        match (wedges.len(), open, seams) {
+            (1, 0, 0) => Kind::Interior,
+            (1, 2, 0) => Kind::Border,
+            (1, 0, 2) | (2, 0, 2) => Kind::Seam,
+            _ => Kind::Locked,
+        }
+    }
+
+    /// The cheapest valid collapse of `node` onto one of its neighbours.
+    fn best_collapse(&self, node: u32) -> Option<Collapse> {
+        let edges = self.edges(node);
+        let kind = self.kind(node, &edges);
+        if kind == Kind::Locked {
+            return None;
+        }
+        let mut best: Option<Collapse> = None;
+        for (target, shared) in edges.iter() {
+            let (target, shared) = (*target, shared.as_slice());
+            let remap = match (kind, shared.len()) {
+                (Kind::Interior, 2) => {
+                    vec![(self.wedge(shared[0], node), self.wedge(shared[0], target))]
+                }
+                (Kind::Border, 1) => {
+                    vec![(self.wedge(shared[0], node), self.wedge(shared[0], target))]
+                }
+                (Kind::Seam, 2) if self.is_seam(node, target, shared) => {
+                    let from = [self.wedge(shared[0], node), self.wedge(shared[1], node)];
+                    let to = [self.wedge(shared[0], target), self.wedge(shared[1], target)];
+                    if from[0] != from[1] {
+                        vec![(from[0], to[0]), (from[1], to[1])]
+                    } else if to[0] == to[1] {
+                        vec![(from[0], to[0])]
+                    } else {
+                        // a single vertex can't follow both sides of the seam
+                        continue;
+                    }
+                }
+                _ => continue,
+            };
+            let mut quadric = self.quadrics[node as usize];
+            quadric.add(&self.quadrics[target as usize]);
+            let cost = quadric.error(self.positions[target as usize]);
+            if best.as_ref().map_or(true, |best| cost < best.cost)
+                && self.is_valid(node, &edges, target, shared)
+            {
+                best = Some(Collapse {
+                    cost,
+                    target,
+                    remap,
+                });
+            }
+        }
+        best
+    }
+
+    /// Whether moving `node` onto `target` keeps the surface manifold and doesn't fold any triangle.
+    fn is_valid(
+        &self,
+        node: u32,
+        edges: &[(u32, Vec<usize>)],
+        target: u32,
+        shared: &[usize],
+    ) -> bool {
+        // the only neighbours in common are the opposite corners of the triangles that disappear
+        let target_neighbours = self.neighbours(target);
+        let common = edges
+            .iter()
+            .filter(|(n, _)| target_neighbours.binary_search(n).is_ok())
+            .count();
+        if common != shared.len() {
+            return false;
+        }
+
+        let moved = self.positions[target as usize];
+        for t in self.live_triangles(node) {
+            if shared.contains(&t) {
+                continue;
+            }
+            let nodes = self.triangle_nodes[t];
+            let p = self.node_positions(nodes);
+            let mut q = p;
+            for k in 0..3 {
+                if nodes[k] == node {
+                    q[k] = moved;
+                }
+            }
+            let before = math::normalize(math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0])));
+            let after = math::normalize(math::cross(math::sub(q[1], q[0]), math::sub(q[2], q[0])));
+            if math::dot(before, after) < MIN_NORMAL_COS {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn collapse(&mut self, node: u32, collapse: Collapse) {
+        let target = collapse.target;
+        let triangles = std::mem::take(&mut self.node_triangles[node as usize]);
+        for t in triangles.into_iter().map(|t| t as usize) {
+            if !self.alive[t] {
+                continue;
+            }
+            let nodes = self.triangle_nodes[t];
+            if nodes.contains(&target) {
+                self.alive[t] = false;
+                self.triangles_left -= 1;
+                continue;
+            }
+            for k in 0..3 {
+                if nodes[k] == node {
+                    let wedge = self.triangles[t][k];
+                    let (_, replacement) = collapse
+                        .remap
+                        .iter()
+                        .copied()
+                        .find(|&(from, _)| from == wedge)
+                        .unwrap_or(collapse.remap[0]);
+                    self.triangles[t][k] = replacement;
+                    self.triangle_nodes[t][k] = target;
+                }
+            }
+            self.node_triangles[target as usize].push(t as u32);
+        }
+        let quadric = self.quadrics[node as usize];
+        self.quadrics[target as usize].add(&quadric);
+        self.removed[node as usize] = true;
+
+        let alive = &self.alive;
+        self.node_triangles[target as usize].retain(|&t| alive[t as usize]);
+    }
+}
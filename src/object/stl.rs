@@ -0,0 +1,322 @@
+//! Loader for STL files, ASCII or binary, as written by CAD programs for 3D printing.
+//! https://www.fabbers.com/tech/STL_Format
+//! STL stores every triangle with its own copy of the vertices, so positions closer than a tolerance are welded
+//! to share the normals generated across the surface. The facet normals of the file are often wrong and are ignored:
+//! normals are generated from the faces, with a crease angle that keeps the edges of mechanical parts sharp.
+//! There are no texture coordinates, the vertices get (0, 0) and a tangent orthogonal to their normal.
+
+use std::collections::HashMap;
+
+use super::bounds::Bounds;
+use super::error::{ObjError, ObjErrorKind};
+use super::math;
+use super::mesh::{Corner, Mesh};
+use super::parser::{self, ObjData};
+use super::triangulate;
+use super::{Primitive, Submesh};
+
+/// Distance under which two positions are welded, as a fraction of the diagonal of the model bounds.
+pub const WELD_TOLERANCE: f32 = 1e-5;
+/// Crease angle (in degrees) used for STL models, see obj::set_crease_angle.
+pub const CREASE_ANGLE: f32 = 30.0;
+
+const HEADER_SIZE: usize = 80;
+const TRIANGLE_SIZE: usize = 50;
+
+/// Load a .stl file whose content is `data`, `path` is used for errors.
+/// `tolerance` is the weld distance relative to the size of the model, 0 welds only identical positions.
+pub fn load(data: &[u8], path: &str, tolerance: f32) -> Result<ObjData, ObjError> {
+    let solids = if is_binary(data) {
+        read_binary(data, path)?
+    } else {
+        read_ascii(data, path)?
+    };
+
+    let positions: Vec<[f32; 3]> = solids
+        .iter()
+        .flat_map(|solid| solid.triangles.iter().flatten().copied())
+        .collect();
+    let (welded, remap) = weld(&positions, tolerance);
+
+    let mut mesh = Mesh {
+        positions: welded,
+        ..Mesh::default()
+    };
+    let mut submeshes = Vec::new();
+    let mut next = 0;
+    for solid in solids {
+        let start = mesh.corners.len();
+        for _ in 0..solid.triangles.len() {
+            let corner = |k: usize| Corner {
+                position: remap[next + k],
+                uv: None,
+                normal: None,
+            };
+            let corners = [corner(0), corner(1), corner(2)];
+            next += 3;
+            // triangles whose corners got welded together have no area left
+            if corners[0].position != corners[1].position
+                && corners[1].position != corners[2].position
+                && corners[2].position != corners[0].position
+            {
+                mesh.push_triangle(corners, 1);
+            }
+        }
+        if mesh.corners.len() > start {
+            submeshes.push(Submesh {
+                object: solid.name,
+                group: None,
+                material: None,
+                primitive: Primitive::Triangles,
+                start,
+                count: mesh.corners.len() - start,
+                visible: true,
+            });
+        }
+    }
+
+    Ok(ObjData {
+        mesh,
+        materials: Vec::new(),
+        submeshes,
+    })
+}
+
+/// The triangles of a `solid` block of an ASCII file, a binary file has a single one without a name.
+struct Solid {
+    name: Option<String>,
+    triangles: Vec<[[f32; 3]; 3]>,
+}
+
+/// Binary files start with an 80 bytes header that may well begin with `solid` like ASCII files,
+/// they are recognized by their size matching the number of triangles that follows the header.
+fn is_binary(data: &[u8]) -> bool {
+    if data.len() < HEADER_SIZE + 4 {
+        return false;
+    }
+    let count = triangles_count(data);
+    let size = HEADER_SIZE as u64 + 4 + count * TRIANGLE_SIZE as u64;
+    let text = String::from_utf8_lossy(&data[..data.len().min(HEADER_SIZE)]);
+    size == data.len() as u64 || !text.trim_start().starts_with("solid")
+}
+
+fn triangles_count(data: &[u8]) -> u64 {
+    let bytes = &data[HEADER_SIZE..HEADER_SIZE + 4];
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64
+}
+
+fn read_binary(data: &[u8], path: &str) -> Result<Vec<Solid>, ObjError> {
+    if data.len() < HEADER_SIZE + 4 {
+        return Err(ObjError::new(
+            path,
+            0,
+            0,
+            ObjErrorKind::BadValue(String::from("the header is truncated")),
+        ));
+    }
+    let count = triangles_count(data) as usize;
+    let body = &data[HEADER_SIZE + 4..];
+    if body.len() / TRIANGLE_SIZE < count {
+        return Err(ObjError::new(
+            path,
+            0,
+            0,
+            ObjErrorKind::BadValue(format!(
+                "the file should have {} triangles but is truncated after {}",
+                count,
+                body.len() / TRIANGLE_SIZE
+            )),
+        ));
+    }
+
+    let float = |bytes: &[u8], i: usize| {
+        f32::from_le_bytes([
+            bytes[4 * i],
+            bytes[4 * i + 1],
+            bytes[4 * i + 2],
+            bytes[4 * i + 3],
+        ])
+    };
+    // each triangle is a normal, three vertices and a 16 bits attribute
+    let triangles = body
+        .chunks_exact(TRIANGLE_SIZE)
+        .take(count)
+        .map(|t| {
+            let vertex = |v: usize| {
+                [
+                    float(t, 3 + 3 * v),
+                    float(t, 4 + 3 * v),
+                    float(t, 5 + 3 * v),
+                ]
+            };
+            [vertex(0), vertex(1), vertex(2)]
+        })
+        .collect();
+    Ok(vec![Solid {
+        name: None,
+        triangles,
+    }])
+}
+
+fn read_ascii(data: &[u8], path: &str) -> Result<Vec<Solid>, ObjError> {
+    let mut solids: Vec<Solid> = Vec::new();
+    let mut polygon: Vec<[f32; 3]> = Vec::new();
+    let mut in_loop = false;
+    for (i, text) in data.split(|&b| b == b'\n').enumerate() {
+        let number = i + 1;
+        let text = String::from_utf8_lossy(text);
+        let mut words = text.split_ascii_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let column = |word: &str| word.as_ptr() as usize - text.as_ptr() as usize + 1;
+        let error =
+            |word: &str, kind: ObjErrorKind| ObjError::new(path, number, column(word), kind);
+
+        if keyword != "solid" && solids.is_empty() {
+            return Err(error(
+                keyword,
+                ObjErrorKind::BadValue(String::from("expected 'solid'")),
+            ));
+        }
+        match keyword {
+            "solid" => {
+                let name = text[column(keyword) - 1 + keyword.len()..].trim();
+                solids.push(Solid {
+                    name: if name.is_empty() {
+                        None
+                    } else {
+                        Some(String::from(name))
+                    },
+                    triangles: Vec::new(),
+                });
+            }
+            "outer" => {
+                polygon.clear();
+                in_loop = true;
+            }
+            "vertex" => {
+                if !in_loop {
+                    return Err(error(
+                        keyword,
+                        ObjErrorKind::BadValue(String::from("vertex outside of a loop")),
+                    ));
+                }
+                let mut position = [0.0; 3];
+                for (k, value) in position.iter_mut().enumerate() {
+                    let word = words.next().ok_or_else(|| {
+                        error(
+                            keyword,
+                            ObjErrorKind::MissingValues {
+                                record: "vertex",
+                                expected: 3,
+                                found: k,
+                            },
+                        )
+                    })?;
+                    *value = parser::parse_f32(word.as_bytes())
+                        .ok_or_else(|| error(word, ObjErrorKind::BadFloat(String::from(word))))?;
+                }
+                polygon.push(position);
+            }
+            "endloop" => {
+                in_loop = false;
+                if polygon.len() < 3 {
+                    return Err(error(
+                        keyword,
+                        ObjErrorKind::UnsupportedFaceArity(polygon.len()),
+                    ));
+                }
+                let triangles = &mut solids.last_mut().unwrap().triangles;
+                for (a, b, c) in triangulate::triangulate(&polygon) {
+                    triangles.push([polygon[a], polygon[b], polygon[c]]);
+                }
+            }
+            "facet" | "endfacet" | "endsolid" => {}
+            _ => {
+                return Err(error(
+                    keyword,
+                    ObjErrorKind::BadValue(format!("unknown keyword '{}'", keyword)),
+                ))
+            }
+        }
+    }
+    if solids.is_empty() {
+        return Err(ObjError::new(
+            path,
+            0,
+            0,
+            ObjErrorKind::BadValue(String::from("the file is empty")),
+        ));
+    }
+    Ok(solids)
+}
+
+/// Merge the positions closer than `tolerance` times the diagonal of their bounds.
+/// Returns the merged positions and, for each of the original ones, the index of the position it became.
+fn weld(positions: &[[f32; 3]], tolerance: f32) -> (Vec<[f32; 3]>, Vec<u32>) {
+    let bounds = match Bounds::new(positions.iter().copied()) {
+        Some(bounds) => bounds,
+        None => return (Vec::new(), Vec::new()),
+    };
+    let min = bounds.min;
+    let distance = tolerance * bounds.diagonal();
+
+    let mut welded: Vec<[f32; 3]> = Vec::new();
+    let mut remap = Vec::with_capacity(positions.len());
+    if !(distance > 0.0) || !distance.is_finite() {
+        // only identical positions
+        let mut seen: HashMap<[u32; 3], u32> = HashMap::new();
+        for p in positions.iter() {
+            let key = [p[0].to_bits(), p[1].to_bits(), p[2].to_bits()];
+            let index = *seen.entry(key).or_insert_with(|| {
+                welded.push(*p);
+                welded.len() as u32 - 1
+            });
+            remap.push(index);
+        }
+        return (welded, remap);
+    }
+
+    // a grid with cells as big as the distance: close positions are in the same cell or in neighbouring ones
+    let mut cells: HashMap<[i64; 3], Vec<u32>> = HashMap::new();
+    let cell_of = |p: &[f32; 3]| {
+        [
+            ((p[0] - min[0]) / distance).floor() as i64,
+            ((p[1] - min[1]) / distance).floor() as i64,
+            ((p[2] - min[2]) / distance).floor() as i64,
+        ]
+    };
+    for p in positions.iter() {
+        let cell = cell_of(p);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    if let Some(candidates) = cells.get(&neighbour) {
+                        for &candidate in candidates.iter() {
+                            let q = welded[candidate as usize];
+                            if math::length(math::sub(*p, q)) <= distance {
+                                found = Some(candidate);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        let index = match found {
+            Some(index) => index,
+            None => {
+                welded.push(*p);
+                let index = welded.len() as u32 - 1;
+                cells.entry(cell).or_insert_with(Vec::new).push(index);
+                index
+            }
+        };
+        remap.push(index);
+    }
+    (welded, remap)
+}
@@ -0,0 +1,435 @@
+//! Subdivision surfaces, to preview low-poly cages smoothed: Catmull-Clark for polygon meshes and Loop for
+//! triangle meshes, with sharp and semi-sharp creases.
+//! https://graphics.pixar.com/library/Geri/paper.pdf (DeRose, Kass, Truong: Subdivision Surfaces in Character Animation)
+
+use super::halfedge::{HalfEdgeMesh, NONE};
+use super::math;
+use super::mesh::{Corner, Mesh};
+use super::triangulate;
+use super::{Primitive, Submesh};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scheme {
+    /// Every face becomes quads, for meshes made of quads and other polygons.
+    CatmullClark,
+    /// Every triangle becomes four, for triangle meshes: polygons are split into triangles first.
+    Loop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Subdivision {
+    pub scheme: Scheme,
+    pub levels: u32,
+    /// Edges between faces at a bigger angle than this (radians) are creases.
+    pub crease_angle: Option<f32>,
+    /// How many levels creases stay sharp for: infinite keeps them sharp, lower values round them off.
+    pub crease_sharpness: f32,
+}
+impl Subdivision {
+    /// `levels` of the scheme that fits `mesh`: Catmull-Clark when it has polygons with more than three sides,
+    /// Loop otherwise.
+    pub fn for_mesh(mesh: &Mesh, levels: u32) -> Subdivision {
+        let scheme = if mesh.polygons.iter().any(|&triangles| triangles > 1) {
+            Scheme::CatmullClark
+        } else {
+            Scheme::Loop
+        };
+        Subdivision {
+            scheme,
+            levels,
+            crease_angle: None,
+            crease_sharpness: f32::INFINITY,
+        }
+    }
+}
+
+/// Subdivide the faces of the triangle submeshes of `mesh`. Points keep their vertex, which only moves when faces
+/// use it too.
+/// Borders are always sharp, and so are the edges between faces of different smoothing groups (other than 0) and,
+/// with a crease angle, between faces at a bigger angle.
+/// Positions and colors follow the rules of the scheme, texture coordinates are interpolated linearly in each face
+/// so that texture seams stay where they are. The result has no normals, they are generated from its smoothing groups.
+pub fn subdivide(
+    mesh: &Mesh,
+    submeshes: &[Submesh],
+    subdivision: &Subdivision,
+) -> (Mesh, Vec<Submesh>) {
+    let polygons = subdivision.scheme == Scheme::CatmullClark;
+    let (mut surface, vertex_of_position) = HalfEdgeMesh::from_mesh(mesh, submeshes, polygons);
+    mark_creases(&mut surface, subdivision);
+    for _ in 0..subdivision.levels {
+        surface = match subdivision.scheme {
+            Scheme::CatmullClark => catmull_clark(&surface),
+            Scheme::Loop => loop_subdivision(&surface),
+        };
+    }
+    let (mut result, ranges) = surface.to_mesh(submeshes.len());
+    // the surface is smooth where there are no creases, even where the cage was flat
+    for group in result.smoothing_groups.iter_mut() {
+        if *group == 0 {
+            *group = 1;
+        }
+    }
+
+    // points come after the triangles, on the vertices of their positions which keep their index at every level
+    let first_point = result.corners.len();
+    for point in mesh.points.iter() {
+        let uv = point.uv.map(|uv| {
+            result.uvs.push(mesh.uvs[uv as usize]);
+            result.uvs.len() as u32 - 1
+        });
+        let normal = point.normal.map(|normal| {
+            result.normals.push(mesh.normals[normal as usize]);
+            result.normals.len() as u32 - 1
+        });
+        result.points.push(Corner {
+            position: vertex_of_position[point.position as usize],
+            uv,
+            normal,
+        });
+    }
+
+    let submeshes = submeshes
+        .iter()
+        .zip(ranges)
+        .map(|(submesh, (start, count))| match submesh.primitive {
+            Primitive::Triangles => Submesh {
+                start,
+                count,
+                ..submesh.clone()
+            },
+            Primitive::Points => Submesh {
+                start: submesh.start - mesh.corners.len() + first_point,
+                ..submesh.clone()
+            },
+        })
+        .collect();
+    (result, submeshes)
+}
+
+/// Give the sharpness of creases to the edges between faces in different smoothing groups or at a bigger angle
+/// than the crease angle.
+fn mark_creases(mesh: &mut HalfEdgeMesh, subdivision: &Subdivision) {
+    let min_cos = subdivision.crease_angle.map(|angle| angle.cos());
+    let normals: Vec<[f32; 3]> = (0..mesh.faces_count())
+        .map(|f| {
+            let points: Vec<[f32; 3]> = mesh
+                .face(f)
+                .map(|h| mesh.positions[mesh.vertex[h] as usize])
+                .collect();
+            math::normalize(triangulate::newell_normal(&points))
+        })
+        .collect();
+    for e in 0..mesh.edges.len() {
+        let h = mesh.edges[e] as usize;
+        if mesh.twin[h] == NONE {
+            continue;
+        }
+        let f1 = mesh.face_of[h] as usize;
+        let f2 = mesh.face_of[mesh.twin[h] as usize] as usize;
+        let (g1, g2) = (mesh.smoothing_groups[f1], mesh.smoothing_groups[f2]);
+        let groups = g1 != 0 && g2 != 0 && g1 != g2;
+        let angle = min_cos.map_or(false, |min_cos| {
+            math::dot(normals[f1], normals[f2]) < min_cos
+        });
+        if groups || angle {
+            mesh.sharpness[e] = subdivision.crease_sharpness;
+        }
+    }
+}
+
+fn catmull_clark(mesh: &HalfEdgeMesh) -> HalfEdgeMesh {
+    let (vertices, edges) = (mesh.positions.len(), mesh.edges.len());
+    let neighbourhoods = mesh.vertex_neighbourhoods();
+    let mut next = HalfEdgeMesh {
+        positions: catmull_clark_points(mesh, &mesh.positions, &neighbourhoods),
+        colors: if mesh.colors.is_empty() {
+            Vec::new()
+        } else {
+            catmull_clark_points(mesh, &mesh.colors, &neighbourhoods)
+        },
+        face_offsets: vec![0],
+        ..HalfEdgeMesh::default()
+    };
+
+    // a quad for each corner of each face: the corner, the middle of its next edge, the center of the face
+    // and the middle of its previous edge
+    let mut sharpness = Vec::with_capacity(4 * mesh.vertex.len());
+    for f in 0..mesh.faces_count() {
+        let center_uv = if mesh.uvs.is_empty() {
+            [0.0, 0.0]
+        } else {
+            let n = mesh.face(f).len() as f32;
+            mesh.face(f).fold([0.0, 0.0], |sum, h| {
+                [sum[0] + mesh.uvs[h][0] / n, sum[1] + mesh.uvs[h][1] / n]
+            })
+        };
+        for h in mesh.face(f) {
+            let previous = mesh.previous(h);
+            let (e, e_previous) = (mesh.edge[h] as usize, mesh.edge[previous] as usize);
+            next.vertex.extend_from_slice(&[
+                mesh.vertex[h],
+                (vertices + e) as u32,
+                (vertices + edges + f) as u32,
+                (vertices + e_previous) as u32,
+            ]);
+            if !mesh.uvs.is_empty() {
+                let uv = mesh.uvs[h];
+                next.uvs.extend_from_slice(&[
+                    uv,
+                    middle_uv(uv, mesh.uvs[mesh.next(h)]),
+                    center_uv,
+                    middle_uv(mesh.uvs[previous], uv),
+                ]);
+            }
+            sharpness.extend_from_slice(&[
+                child_sharpness(mesh, e),
+                0.0,
+                0.0,
+                child_sharpness(mesh, e_previous),
+            ]);
+            next.face_offsets.push(next.vertex.len());
+            next.smoothing_groups.push(mesh.smoothing_groups[f]);
+            next.submeshes.push(mesh.submeshes[f]);
+        }
+    }
+    next.link(&sharpness);
+    next
+}
+
+/// Values of the vertices of the next level: the vertices, then a point on each edge, then one in each face.
+fn catmull_clark_points(
+    mesh: &HalfEdgeMesh,
+    values: &[[f32; 3]],
+    (edges_around, faces_around): &(Vec<Vec<u32>>, Vec<Vec<u32>>),
+) -> Vec<[f32; 3]> {
+    let face_points: Vec<[f32; 3]> = (0..mesh.faces_count())
+        .map(|f| average(mesh.face(f).map(|h| values[mesh.vertex[h] as usize])))
+        .collect();
+    let middle = |e: usize| {
+        let (a, b) = mesh.edge_vertices(e);
+        math::scale(math::add(values[a], values[b]), 0.5)
+    };
+
+    let mut points = Vec::with_capacity(values.len() + mesh.edges.len() + face_points.len());
+    for v in 0..values.len() {
+        let (edges, faces) = (&edges_around[v], &faces_around[v]);
+        let smooth = || {
+            // (Q + 2R + (n - 3)P) / n, with the average Q of the face points and R of the edge middles
+            let n = edges.len() as f32;
+            let q = average(faces.iter().map(|&f| face_points[f as usize]));
+            let r = average(edges.iter().map(|&e| middle(e as usize)));
+            let sum = math::add(q, math::scale(r, 2.0));
+            math::scale(math::add(sum, math::scale(values[v], n - 3.0)), 1.0 / n)
+        };
+        points.push(vertex_point(mesh, values, v, edges, faces, &smooth));
+    }
+    for e in 0..mesh.edges.len() {
+        let smooth = || {
+            let h = mesh.edges[e] as usize;
+            let f1 = mesh.face_of[h] as usize;
+            let f2 = mesh.face_of[mesh.twin[h] as usize] as usize;
+            let faces = math::scale(math::add(face_points[f1], face_points[f2]), 0.5);
+            math::scale(math::add(middle(e), faces), 0.5)
+        };
+        points.push(blend(&smooth, middle(e), mesh.edge_sharpness(e)));
+    }
+    points.extend(face_points);
+    points
+}
+
+fn loop_subdivision(mesh: &HalfEdgeMesh) -> HalfEdgeMesh {
+    let vertices = mesh.positions.len();
+    let neighbourhoods = mesh.vertex_neighbourhoods();
+    let mut next = HalfEdgeMesh {
+        positions: loop_points(mesh, &mesh.positions, &neighbourhoods),
+        colors: if mesh.colors.is_empty() {
+            Vec::new()
+        } else {
+            loop_points(mesh, &mesh.colors, &neighbourhoods)
+        },
+        face_offsets: vec![0],
+        ..HalfEdgeMesh::default()
+    };
+
+    // a triangle at each corner, and one in the middle
+    let mut sharpness = Vec::with_capacity(4 * mesh.vertex.len());
+    let push_face =
+        |next: &mut HalfEdgeMesh, corners: [u32; 3], uvs: Option<[[f32; 2]; 3]>, f: usize| {
+            next.vertex.extend_from_slice(&corners);
+            if let Some(uvs) = uvs {
+                next.uvs.extend_from_slice(&uvs);
+            }
+            next.face_offsets.push(next.vertex.len());
+            next.smoothing_groups.push(mesh.smoothing_groups[f]);
+            next.submeshes.push(mesh.submeshes[f]);
+        };
+    for f in 0..mesh.faces_count() {
+        let face = mesh.face(f);
+        let middle_uvs: Vec<[f32; 2]> = face
+            .clone()
+            .map(|h| {
+                if mesh.uvs.is_empty() {
+                    [0.0, 0.0]
+                } else {
+                    middle_uv(mesh.uvs[h], mesh.uvs[mesh.next(h)])
+                }
+            })
+            .collect();
+        for (i, h) in face.clone().enumerate() {
+            let previous = mesh.previous(h);
+            let (e, e_previous) = (mesh.edge[h] as usize, mesh.edge[previous] as usize);
+            let corners = [
+                mesh.vertex[h],
+                (vertices + e) as u32,
+                (vertices + e_previous) as u32,
+            ];
+            let uvs = if mesh.uvs.is_empty() {
+                None
+            } else {
+                Some([
+                    mesh.uvs[h],
+                    middle_uvs[i],
+                    middle_uvs[(i + face.len() - 1) % face.len()],
+                ])
+            };
+            push_face(&mut next, corners, uvs, f);
+            sharpness.extend_from_slice(&[
+                child_sharpness(mesh, e),
+                0.0,
+                child_sharpness(mesh, e_previous),
+            ]);
+        }
+        if face.len() == 3 {
+            let corners = [
+                (vertices + mesh.edge[face.start] as usize) as u32,
+                (vertices + mesh.edge[face.start + 1] as usize) as u32,
+                (vertices + mesh.edge[face.start + 2] as usize) as u32,
+            ];
+            let uvs = if mesh.uvs.is_empty() {
+                None
+            } else {
+                Some([middle_uvs[0], middle_uvs[1], middle_uvs[2]])
+            };
+            push_face(&mut next, corners, uvs, f);
+            sharpness.extend_from_slice(&[0.0, 0.0, 0.0]);
+        }
+    }
+    next.link(&sharpness);
+    next
+}
+
+/// Values of the vertices of the next level: the vertices, then a point on each edge.
+fn loop_points(
+    mesh: &HalfEdgeMesh,
+    values: &[[f32; 3]],
+    (edges_around, faces_around): &(Vec<Vec<u32>>, Vec<Vec<u32>>),
+) -> Vec<[f32; 3]> {
+    let mut points = Vec::with_capacity(values.len() + mesh.edges.len());
+    for v in 0..values.len() {
+        let edges = &edges_around[v];
+        let smooth = || {
+            // (1 - n * beta) * P + beta * the sum of the neighbours, with Warren's beta
+            let n = edges.len() as f32;
+            let beta = if edges.len() == 3 {
+                3.0 / 16.0
+            } else {
+                3.0 / (8.0 * n)
+            };
+            let neighbours = edges.iter().fold([0.0; 3], |sum, &e| {
+                let (a, b) = mesh.edge_vertices(e as usize);
+                math::add(sum, values[if a == v { b } else { a }])
+            });
+            math::add(
+                math::scale(values[v], 1.0 - n * beta),
+                math::scale(neighbours, beta),
+            )
+        };
+        points.push(vertex_point(
+            mesh,
+            values,
+            v,
+            edges,
+            &faces_around[v],
+            &smooth,
+        ));
+    }
+    for e in 0..mesh.edges.len() {
+        let (a, b) = mesh.edge_vertices(e);
+        let middle = math::scale(math::add(values[a], values[b]), 0.5);
+        let smooth = || {
+            // 3/8 of the ends and 1/8 of the opposite corners of the two triangles
+            let h = mesh.edges[e] as usize;
+            let c = mesh.vertex[mesh.previous(h)] as usize;
+            let d = mesh.vertex[mesh.previous(mesh.twin[h] as usize)] as usize;
+            math::add(
+                math::scale(math::add(values[a], values[b]), 3.0 / 8.0),
+                math::scale(math::add(values[c], values[d]), 1.0 / 8.0),
+            )
+        };
+        points.push(blend(&smooth, middle, mesh.edge_sharpness(e)));
+    }
+    points
+}
+
+/// The vertex rules shared by both schemes: smooth vertices use `smooth`, vertices on two sharp edges move along
+/// them, vertices on more sharp edges and the corners of borders stay in place. Semi-sharp edges blend the two.
+fn vertex_point(
+    mesh: &HalfEdgeMesh,
+    values: &[[f32; 3]],
+    v: usize,
+    edges: &[u32],
+    faces: &[u32],
+    smooth: &dyn Fn() -> [f32; 3],
+) -> [f32; 3] {
+    let p = values[v];
+    let sharp: Vec<usize> = edges
+        .iter()
+        .map(|&e| e as usize)
+        .filter(|&e| mesh.edge_sharpness(e) > 0.0)
+        .collect();
+    if sharp.len() < 2 {
+        return if edges.is_empty() { p } else { smooth() };
+    }
+    let sharpness = sharp.iter().map(|&e| mesh.edge_sharpness(e)).sum::<f32>() / sharp.len() as f32;
+    let rule = if sharp.len() == 2 && faces.len() > 1 {
+        let other = |e: usize| {
+            let (a, b) = mesh.edge_vertices(e);
+            values[if a == v { b } else { a }]
+        };
+        let ends = math::add(other(sharp[0]), other(sharp[1]));
+        math::scale(math::add(ends, math::scale(p, 6.0)), 1.0 / 8.0)
+    } else {
+        p
+    };
+    blend(smooth, rule, sharpness)
+}
+
+/// The sharp rule for sharpness from 1, mixed with the smooth one below.
+fn blend(smooth: &dyn Fn() -> [f32; 3], sharp: [f32; 3], sharpness: f32) -> [f32; 3] {
+    if sharpness >= 1.0 {
+        return sharp;
+    }
+    let smooth = smooth();
+    math::add(smooth, math::scale(math::sub(sharp, smooth), sharpness))
+}
+
+/// The sharpness of the halves of an edge at the next level.
+fn child_sharpness(mesh: &HalfEdgeMesh, e: usize) -> f32 {
+    (mesh.sharpness[e] - 1.0).max(0.0)
+}
+
+fn average<I: Iterator<Item = [f32; 3]>>(values: I) -> [f32; 3] {
+    let (sum, count) = values.fold(([0.0; 3], 0), |(sum, count), value| {
+        (math::add(sum, value), count + 1)
+    });
+    if count > 0 {
+        math::scale(sum, 1.0 / count as f32)
+    } else {
+        sum
+    }
+}
+
+fn middle_uv(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [(a[0] + b[0]) * 0.5, (a[1] + b[1]) * 0.5]
+}
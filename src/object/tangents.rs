@@ -0,0 +1,283 @@
+//! Tangent generation for normal mapping.
+//! http://www.terathon.com/code/tangent.html
+//! http://www.mikktspace.com/
+
+use std::collections::HashMap;
+
+use super::math;
+use super::mesh::Mesh;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TangentMode {
+    /// Average of the tangents of the triangles sharing a vertex, weighted by their area.
+    Smooth,
+    /// The tangent space of MikkTSpace, used by Blender and Substance to bake normal maps, see mikktspace.
+    MikkTSpace,
+}
+
+/// Tangent of every corner of the mesh as (x, y, z, w): xyz is orthogonal to the corner normal and w (1 or -1)
+/// is the handedness of the tangent frame, so that the bitangent is `w * cross(normal, tangent)`.
+/// `normals` has the normal of each corner. Corners sharing position, texture coordinates and normal
+/// are the same vertex and get the same tangent.
+pub fn tangents(mesh: &Mesh, normals: &[[f32; 3]], mode: TangentMode) -> Vec<[f32; 4]> {
+    if mode == TangentMode::MikkTSpace {
+        return mikktspace(mesh, normals);
+    }
+    let triangles = mesh.triangles_count();
+
+    // tangent and bitangent of each triangle, None when its texture coordinates are degenerate
+    let face_tangents: Vec<Option<([f32; 3], [f32; 3])>> =
+        (0..triangles).map(|t| face_tangent(mesh, t)).collect();
+
+    // group the corners into vertices
+    let mut vertex_of_corner: Vec<usize> = Vec::with_capacity(mesh.corners.len());
+    let mut vertices: HashMap<(u32, Option<u32>, [u32; 3]), usize> = HashMap::new();
+    for (c, corner) in mesh.corners.iter().enumerate() {
+        let n = normals[c];
+        let key = (
+            corner.position,
+            corner.uv,
+            [n[0].to_bits(), n[1].to_bits(), n[2].to_bits()],
+        );
+        let next = vertices.len();
+        vertex_of_corner.push(*vertices.entry(key).or_insert(next));
+    }
+
+    // accumulate the contribution of each triangle to its vertices
+    let mut tangent_sums = vec![[0.0f32; 3]; vertices.len()];
+    let mut bitangent_sums = vec![[0.0f32; 3]; vertices.len()];
+    for (c, &v) in vertex_of_corner.iter().enumerate() {
+        let (t, b) = match face_tangents[c / 3] {
+            Some(frame) => frame,
+            None => continue,
+        };
+        let area = math::length(mesh.face_normal(c / 3)) / 2.0;
+        let t = math::scale(math::normalize(t), area);
+        let b = math::scale(math::normalize(b), area);
+        tangent_sums[v] = math::add(tangent_sums[v], t);
+        bitangent_sums[v] = math::add(bitangent_sums[v], b);
+    }
+
+    // Gram-Schmidt orthogonalization against the normal, and handedness
+    vertex_of_corner
+        .iter()
+        .enumerate()
+        .map(|(c, &v)| {
+            let n = normals[c];
+            let t = tangent_sums[v];
+            let t = math::normalize(math::sub(t, math::scale(n, math::dot(n, t))));
+            let t = if math::length(t) > 0.5 {
+                t
+            } else {
+                any_perpendicular(n)
+            };
+            let w = if math::dot(math::cross(n, t), bitangent_sums[v]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            [t[0], t[1], t[2], w]
+        })
+        .collect()
+}
+
+/// Tangents computed like the reference implementation of MikkTSpace (mikktspace.c, with its default angular
+/// threshold), so that normal maps baked by other tools are shown without seams or shading errors:
+/// - corners with the same position, normal and texture coordinates (by value) are the same vertex;
+/// - around a vertex, triangles are averaged together only when they are connected through edges they share
+///   with opposite windings and have texture coordinates with the same orientation, so that mirrored UVs and
+///   separate fans of faces get tangents of their own;
+/// - each triangle contributes its tangent projected on the corner normal, weighted by its angle at the corner
+///   measured in the plane of the normal;
+/// - the sign of the bitangent is the orientation of the texture coordinates of the triangle;
+/// - triangles without tangent (texture coordinates on a line) take the one of another triangle at the same vertex.
+pub fn mikktspace(mesh: &Mesh, normals: &[[f32; 3]]) -> Vec<[f32; 4]> {
+    let triangles = mesh.triangles_count();
+    let face_tangents: Vec<Option<([f32; 3], [f32; 3])>> =
+        (0..triangles).map(|t| face_tangent(mesh, t)).collect();
+    // the texture coordinates keep their orientation when the tangent frame is right-handed
+    let preserving: Vec<bool> = (0..triangles)
+        .map(|t| match face_tangents[t] {
+            Some((tangent, bitangent)) => {
+                math::dot(math::cross(tangent, bitangent), mesh.face_normal(t)) > 0.0
+            }
+            None => false,
+        })
+        .collect();
+
+    // weld the corners into vertices by value
+    let mut vertices: HashMap<[u32; 8], u32> = HashMap::new();
+    let vertex: Vec<u32> = (0..mesh.corners.len())
+        .map(|c| {
+            let p = mesh.corner_position(c);
+            let n = normals[c];
+            let uv = mesh.corner_uv(c).unwrap_or([0.0, 0.0]);
+            let key = [
+                p[0].to_bits(),
+                p[1].to_bits(),
+                p[2].to_bits(),
+                n[0].to_bits(),
+                n[1].to_bits(),
+                n[2].to_bits(),
+                uv[0].to_bits(),
+                uv[1].to_bits(),
+            ];
+            let next = vertices.len() as u32;
+            *vertices.entry(key).or_insert(next)
+        })
+        .collect();
+
+    // corners of neighbouring triangles at the same vertex end up in the same group
+    let mut group: Vec<usize> = (0..mesh.corners.len()).collect();
+    let mut edges: HashMap<(u32, u32), usize> = HashMap::new();
+    for c in 0..mesh.corners.len() {
+        if face_tangents[c / 3].is_some() {
+            let next = 3 * (c / 3) + (c + 1) % 3;
+            edges.insert((vertex[c], vertex[next]), c);
+        }
+    }
+    for (&(a, b), &c) in edges.iter() {
+        let other = match edges.get(&(b, a)) {
+            Some(&other) if preserving[other / 3] == preserving[c / 3] && other / 3 != c / 3 => {
+                other
+            }
+            _ => continue,
+        };
+        // c goes from a to b, other from b to a
+        let c_next = 3 * (c / 3) + (c + 1) % 3;
+        let other_next = 3 * (other / 3) + (other + 1) % 3;
+        union(&mut group, c, other_next);
+        union(&mut group, c_next, other);
+    }
+
+    // sum the contributions of each group
+    let mut tangent_sums = vec![[0.0f32; 3]; mesh.corners.len()];
+    for c in 0..mesh.corners.len() {
+        let tangent = match face_tangents[c / 3] {
+            Some((tangent, _)) => tangent,
+            None => continue,
+        };
+        let n = normals[c];
+        let project = |v: [f32; 3]| math::normalize(math::sub(v, math::scale(n, math::dot(n, v))));
+        let p = mesh.corner_position(c);
+        let next = mesh.corner_position(3 * (c / 3) + (c + 1) % 3);
+        let previous = mesh.corner_position(3 * (c / 3) + (c + 2) % 3);
+        let cos = math::dot(project(math::sub(next, p)), project(math::sub(previous, p)));
+        let angle = cos.clamp(-1.0, 1.0).acos();
+        let g = find(&mut group, c);
+        tangent_sums[g] = math::add(tangent_sums[g], math::scale(project(tangent), angle));
+    }
+
+    // a group of some triangle at each vertex, for the triangles without tangent
+    let mut any_group: HashMap<u32, usize> = HashMap::new();
+    for c in 0..mesh.corners.len() {
+        if face_tangents[c / 3].is_some() {
+            let g = find(&mut group, c);
+            any_group.entry(vertex[c]).or_insert(g);
+        }
+    }
+
+    (0..mesh.corners.len())
+        .map(|c| {
+            let n = normals[c];
+            let g = if face_tangents[c / 3].is_some() {
+                Some(find(&mut group, c))
+            } else {
+                any_group.get(&vertex[c]).copied()
+            };
+            let t = g.map_or([0.0; 3], |g| {
+                let t = tangent_sums[g];
+                math::normalize(math::sub(t, math::scale(n, math::dot(n, t))))
+            });
+            let t = if math::length(t) > 0.5 {
+                t
+            } else {
+                any_perpendicular(n)
+            };
+            let w = match g {
+                Some(g) if !preserving[g / 3] => -1.0,
+                _ => 1.0,
+            };
+            [t[0], t[1], t[2], w]
+        })
+        .collect()
+}
+
+/// Representative of the group of `i`, see union.
+fn find(group: &mut [usize], mut i: usize) -> usize {
+    while group[i] != i {
+        group[i] = group[group[i]];
+        i = group[i];
+    }
+    i
+}
+
+/// Put `a` and `b` in the same group: the representative of a group is the smallest of its members, so that
+/// it belongs to a triangle of the group.
+fn union(group: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(group, a), find(group, b));
+    if a < b {
+        group[b] = a;
+    } else {
+        group[a] = b;
+    }
+}
+
+/// Tangent and bitangent of a triangle from its positions and texture coordinates.
+fn face_tangent(mesh: &Mesh, triangle: usize) -> Option<([f32; 3], [f32; 3])> {
+    let c = 3 * triangle;
+    let (uv1, uv2, uv3) = match (
+        mesh.corner_uv(c),
+        mesh.corner_uv(c + 1),
+        mesh.corner_uv(c + 2),
+    ) {
+        (Some(uv1), Some(uv2), Some(uv3)) => (uv1, uv2, uv3),
+        _ => return None,
+    };
+
+    let edge1 = math::sub(mesh.corner_position(c + 1), mesh.corner_position(c));
+    let edge2 = math::sub(mesh.corner_position(c + 2), mesh.corner_position(c));
+    let d_uv1 = (uv2[0] - uv1[0], uv2[1] - uv1[1]);
+    let d_uv2 = (uv3[0] - uv1[0], uv3[1] - uv1[1]);
+
+    let det = d_uv1.0 * d_uv2.1 - d_uv2.0 * d_uv1.1;
+    if det.is_nan() || det.abs() <= f32::EPSILON * 1e-3 {
+        // all the texture coordinates on a line or NaN: the triangle has no tangent
+        return None;
+    }
+    let f = 1.0 / det;
+
+    let tangent = math::scale(
+        math::sub(math::scale(edge1, d_uv2.1), math::scale(edge2, d_uv1.1)),
+        f,
+    );
+    let bitangent = math::scale(
+        math::sub(math::scale(edge2, d_uv1.0), math::scale(edge1, d_uv2.0)),
+        f,
+    );
+
+    if tangent
+        .iter()
+        .chain(bitangent.iter())
+        .all(|x| x.is_finite())
+    {
+        Some((tangent, bitangent))
+    } else {
+        None
+    }
+}
+
+/// Some unit vector orthogonal to n, used when the texture coordinates don't define a tangent.
+fn any_perpendicular(n: [f32; 3]) -> [f32; 3] {
+    let axis = if n[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let t = math::normalize(math::cross(axis, n));
+    if math::length(t) > 0.5 {
+        t
+    } else {
+        [1.0, 0.0, 0.0]
+    }
+}
@@ -0,0 +1,117 @@
+//! Transformations of a whole mesh, to bring models made with other conventions to the one of the viewer:
+//! Y up, right-handed, about the size of the unit cube.
+
+use super::bounds::Bounds;
+use super::math::{self, Matrix};
+use super::mesh::Mesh;
+
+/// The axis pointing up in the file: Y for most DCC tools and glTF, Z for CAD tools and Blender's own space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+/// Handedness of the coordinate system of the file, the viewer is right-handed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// The transformations applied when a model is loaded, in this order: axis conversion, recenter, scale.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImportTransform {
+    pub up: UpAxis,
+    pub handedness: Handedness,
+    /// Move the center of the bounds to the origin.
+    pub recenter: bool,
+    /// Scale the model so that the largest side of its bounds is 1.
+    pub unit_size: bool,
+}
+impl Default for ImportTransform {
+    fn default() -> ImportTransform {
+        ImportTransform {
+            up: UpAxis::Y,
+            handedness: Handedness::Right,
+            recenter: false,
+            unit_size: false,
+        }
+    }
+}
+impl ImportTransform {
+    /// Whether the transformation leaves the model as it is.
+    pub fn is_identity(&self) -> bool {
+        *self == ImportTransform::default()
+    }
+
+    /// The matrix applying the transformation to a mesh with `bounds`.
+    pub fn matrix(&self, bounds: Option<Bounds>) -> Matrix {
+        let conversion = axis_conversion(self.up, self.handedness);
+        // the conversion only swaps and flips axes: the bounds stay aligned and two corners are enough
+        let converted = bounds.and_then(|b| {
+            Bounds::new(vec![
+                math::transform_point(&conversion, b.min),
+                math::transform_point(&conversion, b.max),
+            ])
+        });
+        let bounds = match converted {
+            Some(bounds) => bounds,
+            None => return conversion,
+        };
+
+        let mut translation = [0.0; 3];
+        if self.recenter {
+            translation = math::scale(math::add(bounds.min, bounds.max), -0.5);
+        }
+        let size = bounds.size();
+        let largest = size[0].max(size[1]).max(size[2]);
+        let scale = if self.unit_size && largest > 0.0 {
+            1.0 / largest
+        } else {
+            1.0
+        };
+        let placement = math::from_translation_rotation_scale(
+            math::scale(translation, scale),
+            [0.0, 0.0, 0.0, 1.0],
+            [scale; 3],
+        );
+        math::multiply(&placement, &conversion)
+    }
+}
+
+/// The rotation (and mirroring) from the axes of a file to Y up and right-handed.
+pub fn axis_conversion(up: UpAxis, handedness: Handedness) -> Matrix {
+    // columns are the images of x, y and z
+    let (x, y, z) = match (up, handedness) {
+        (UpAxis::Y, Handedness::Right) => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+        // z is flipped, keeping x on the right and y up
+        (UpAxis::Y, Handedness::Left) => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+        // -90 degrees around x: z becomes y and y becomes -z
+        (UpAxis::Z, Handedness::Right) => ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        // the same rotation with y flipped
+        (UpAxis::Z, Handedness::Left) => ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    };
+    let mut m = math::IDENTITY;
+    for (column, axis) in [x, y, z].iter().enumerate() {
+        m[4 * column..4 * column + 3].copy_from_slice(axis);
+    }
+    m
+}
+
+/// Apply `m` to the positions and normals of `mesh`.
+/// Matrices that mirror the space also reverse the winding of the triangles, so that their front faces stay
+/// outside. Tangents are built from the transformed mesh and need nothing else.
+pub fn transform(mesh: &mut Mesh, m: &Matrix) {
+    for p in mesh.positions.iter_mut() {
+        *p = math::transform_point(m, *p);
+    }
+    for n in mesh.normals.iter_mut() {
+        *n = math::transform_normal(m, *n);
+    }
+    if math::determinant(m) < 0.0 {
+        for triangle in mesh.corners.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+}
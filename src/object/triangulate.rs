@@ -0,0 +1,115 @@
+//! Ear clipping triangulation for polygon faces.
+//! https://www.geometrictools.com/Documentation/TriangulationByEarClipping.pdf
+
+use super::math::{cross, dot, normalize};
+
+/// Normal of the best-fit plane of a polygon computed with Newell's method.
+/// The length of the result is twice the area of the polygon, the direction follows its winding.
+pub fn newell_normal(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut normal = [0.0, 0.0, 0.0];
+    for (i, current) in points.iter().enumerate() {
+        let next = &points[(i + 1) % points.len()];
+        normal[0] += (current[1] - next[1]) * (current[2] + next[2]);
+        normal[1] += (current[2] - next[2]) * (current[0] + next[0]);
+        normal[2] += (current[0] - next[0]) * (current[1] + next[1]);
+    }
+    normal
+}
+
+/// Split a polygon into triangles, returning triples of indices into `points`.
+/// The polygon can be concave and does not need to be planar: it gets projected onto its best-fit plane.
+/// The winding of the polygon is preserved in every triangle.
+pub fn triangulate(points: &[[f32; 3]]) -> Vec<(usize, usize, usize)> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+    if n == 3 {
+        return vec![(0, 1, 2)];
+    }
+
+    let normal = newell_normal(points);
+    let length = dot(normal, normal).sqrt();
+    if !(length > 0.0) {
+        // the polygon has no area, a fan is as good as anything else
+        return (1..n - 1).map(|i| (0, i, i + 1)).collect();
+    }
+    let normal = [normal[0] / length, normal[1] / length, normal[2] / length];
+
+    // orthonormal basis (u, v) of the plane, with u x v = normal so that the winding stays counter-clockwise
+    let helper = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let u = normalize(cross(helper, normal));
+    let v = cross(normal, u);
+
+    let projected: Vec<(f32, f32)> = points.iter().map(|p| (dot(*p, u), dot(*p, v))).collect();
+
+    // tolerance relative to the size of the polygon
+    let epsilon = length * 1e-7;
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let mut ear = None;
+        let mut fallback = None;
+
+        for i in 0..count {
+            let prev = remaining[(i + count - 1) % count];
+            let current = remaining[i];
+            let next = remaining[(i + 1) % count];
+            let (a, b, c) = (projected[prev], projected[current], projected[next]);
+
+            let area = area2(a, b, c);
+            if area <= epsilon {
+                // reflex or degenerate corner
+                continue;
+            }
+            if fallback.is_none() {
+                fallback = Some(i);
+            }
+
+            let contains_other = remaining.iter().any(|&other| {
+                other != prev
+                    && other != current
+                    && other != next
+                    && point_in_triangle(projected[other], a, b, c, epsilon)
+            });
+            if !contains_other {
+                ear = Some(i);
+                break;
+            }
+        }
+
+        // self-intersecting or numerically degenerate polygons may have no proper ear:
+        // clip a convex corner anyway, or the first corner if none is left, so that we always make progress
+        let i = ear.or(fallback).unwrap_or(0);
+        let prev = remaining[(i + count - 1) % count];
+        let next = remaining[(i + 1) % count];
+        triangles.push((prev, remaining[i], next));
+        remaining.remove(i);
+    }
+    triangles.push((remaining[0], remaining[1], remaining[2]));
+
+    triangles
+}
+
+/// Twice the signed area of the 2D triangle abc, positive when counter-clockwise.
+fn area2(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)
+}
+
+/// Whether p lies inside or on the border of the counter-clockwise triangle abc.
+fn point_in_triangle(
+    p: (f32, f32),
+    a: (f32, f32),
+    b: (f32, f32),
+    c: (f32, f32),
+    epsilon: f32,
+) -> bool {
+    area2(a, b, p) >= -epsilon && area2(b, c, p) >= -epsilon && area2(c, a, p) >= -epsilon
+}
@@ -0,0 +1,354 @@
+//! Texture coordinates generated for meshes without them, so that they can be textured and get tangents for
+//! normal mapping: projections of the whole mesh, or an automatic unwrap into flat charts packed in the unit square.
+
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+
+use super::bounds::Bounds;
+use super::math;
+use super::mesh::Mesh;
+
+/// Largest angle between the normal of a triangle and the average normal of the chart it joins, in degrees.
+/// Charts are projected along their average normal: the smaller the angle, the less the triangles are stretched.
+pub const CHART_ANGLE: f32 = 45.0;
+
+/// Space left around each chart, as a fraction of the side of the packed charts, so that textures filtered
+/// near the border of a chart don't bleed into its neighbours.
+pub const CHART_PADDING: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Along the axis in which the mesh is the thinnest, for flat models.
+    Planar,
+    /// Around the vertical axis through the center of the mesh, v going up.
+    Cylindrical,
+    /// Around the center of the mesh, v going from the bottom pole to the top one.
+    Spherical,
+    /// Each triangle along the axis closest to its normal, the tri-planar mapping of the six faces of a box.
+    Box,
+    /// Neighbouring triangles facing about the same way are grouped into charts, each one is projected along
+    /// its average normal and the charts are packed without overlapping.
+    Unwrap,
+}
+
+/// Replace the texture coordinates of the triangles with new ones made with `projection`.
+/// The projections keep the proportions of the mesh: its largest side spans the whole texture.
+/// Corners with the same coordinates share them. Points lose their texture coordinates.
+pub fn generate(mesh: &mut Mesh, projection: Projection) {
+    let bounds = match Bounds::new((0..mesh.corners.len()).map(|c| mesh.corner_position(c))) {
+        Some(bounds) => bounds,
+        None => return,
+    };
+    let uvs = match projection {
+        Projection::Planar => planar(mesh, &bounds),
+        Projection::Cylindrical => around(mesh, &bounds, false),
+        Projection::Spherical => around(mesh, &bounds, true),
+        Projection::Box => box_mapping(mesh, &bounds),
+        Projection::Unwrap => unwrap(mesh),
+    };
+
+    let mut indices: HashMap<[u32; 2], u32> = HashMap::new();
+    mesh.uvs.clear();
+    for (corner, uv) in mesh.corners.iter_mut().zip(uvs) {
+        let key = [(uv[0] + 0.0).to_bits(), (uv[1] + 0.0).to_bits()];
+        let next = mesh.uvs.len() as u32;
+        let index = *indices.entry(key).or_insert(next);
+        if index == next {
+            mesh.uvs.push(uv);
+        }
+        corner.uv = Some(index);
+    }
+    for point in mesh.points.iter_mut() {
+        point.uv = None;
+    }
+}
+
+/// Position of `p` in the cube around `bounds`, from 0 to 1 along the largest side.
+fn in_unit_cube(p: [f32; 3], bounds: &Bounds) -> [f32; 3] {
+    let size = bounds.size();
+    let largest = size[0].max(size[1]).max(size[2]);
+    let center = math::scale(math::add(bounds.min, bounds.max), 0.5);
+    let scale = if largest > 0.0 { 1.0 / largest } else { 1.0 };
+    math::add(math::scale(math::sub(p, center), scale), [0.5; 3])
+}
+
+/// Coordinates of `q` seen along `axis`, from the positive side or from the negative one, with u to the right and
+/// v up so that textures are not mirrored.
+fn along_axis(q: [f32; 3], axis: usize, positive: bool) -> [f32; 2] {
+    match (axis, positive) {
+        (0, true) => [1.0 - q[2], q[1]],
+        (0, false) => [q[2], q[1]],
+        (1, true) => [q[0], 1.0 - q[2]],
+        (1, false) => [q[0], q[2]],
+        (_, true) => [q[0], q[1]],
+        (_, false) => [1.0 - q[0], q[1]],
+    }
+}
+
+fn planar(mesh: &Mesh, bounds: &Bounds) -> Vec<[f32; 2]> {
+    let size = bounds.size();
+    let axis = (0..3).min_by(|&a, &b| size[a].total_cmp(&size[b])).unwrap();
+    (0..mesh.corners.len())
+        .map(|c| along_axis(in_unit_cube(mesh.corner_position(c), bounds), axis, true))
+        .collect()
+}
+
+fn box_mapping(mesh: &Mesh, bounds: &Bounds) -> Vec<[f32; 2]> {
+    (0..mesh.corners.len())
+        .map(|c| {
+            let n = mesh.face_normal(c / 3);
+            let axis = (0..3)
+                .max_by(|&a, &b| n[a].abs().total_cmp(&n[b].abs()))
+                .unwrap();
+            let q = in_unit_cube(mesh.corner_position(c), bounds);
+            along_axis(q, axis, n[axis] >= 0.0)
+        })
+        .collect()
+}
+
+/// Cylindrical or spherical projection: u is the angle around the vertical axis, v the height or the latitude.
+/// Triangles across the seam at the back get u above 1 instead of spanning the whole texture, and corners on the
+/// axis (the poles of a sphere) take the u of the rest of their triangle.
+fn around(mesh: &Mesh, bounds: &Bounds, spherical: bool) -> Vec<[f32; 2]> {
+    let center = math::scale(math::add(bounds.min, bounds.max), 0.5);
+    let size = bounds.size();
+    let largest = size[0].max(size[1]).max(size[2]);
+    let on_axis = 1e-6 * largest;
+
+    let mut uvs = Vec::with_capacity(mesh.corners.len());
+    let mut poles = [false; 3];
+    for triangle in 0..mesh.triangles_count() {
+        let first = uvs.len();
+        for (k, pole) in poles.iter_mut().enumerate() {
+            let p = mesh.corner_position(3 * triangle + k);
+            let d = math::sub(p, center);
+            let u = 0.5 + d[0].atan2(d[2]) / (2.0 * PI);
+            let v = if spherical {
+                let r = math::length(d);
+                if r > 0.0 {
+                    0.5 + (d[1] / r).clamp(-1.0, 1.0).asin() / PI
+                } else {
+                    0.5
+                }
+            } else {
+                in_unit_cube(p, bounds)[1]
+            };
+            *pole = (d[0] * d[0] + d[2] * d[2]).sqrt() <= on_axis;
+            uvs.push([u, v]);
+        }
+
+        let corners = &mut uvs[first..];
+        let highest = (0..3)
+            .filter(|&k| !poles[k])
+            .map(|k| corners[k][0])
+            .fold(f32::NEG_INFINITY, f32::max);
+        let mut sum = 0.0;
+        let mut count = 0;
+        for k in (0..3).filter(|&k| !poles[k]) {
+            if highest - corners[k][0] > 0.5 {
+                corners[k][0] += 1.0;
+            }
+            sum += corners[k][0];
+            count += 1;
+        }
+        if count > 0 {
+            for k in (0..3).filter(|&k| poles[k]) {
+                corners[k][0] = sum / count as f32;
+            }
+        }
+    }
+    uvs
+}
+
+/// A group of triangles projected together, with the coordinates of their corners relative to the corner of the
+/// rectangle around them.
+struct Chart {
+    triangles: Vec<usize>,
+    uvs: Vec<[f32; 2]>,
+    size: [f32; 2],
+}
+
+fn unwrap(mesh: &Mesh) -> Vec<[f32; 2]> {
+    let charts: Vec<Chart> = grow_charts(mesh)
+        .into_iter()
+        .map(|triangles| flatten(mesh, triangles))
+        .collect();
+    let (offsets, scale) = pack(&charts);
+
+    let mut uvs = vec![[0.0; 2]; mesh.corners.len()];
+    for (chart, offset) in charts.iter().zip(offsets) {
+        for (i, &t) in chart.triangles.iter().enumerate() {
+            for k in 0..3 {
+                let uv = chart.uvs[3 * i + k];
+                uvs[3 * t + k] = [(offset[0] + uv[0]) * scale, (offset[1] + uv[1]) * scale];
+            }
+        }
+    }
+    uvs
+}
+
+/// The triangles of each chart: a chart grows across the edges shared by two triangles, as long as the triangles
+/// face less than CHART_ANGLE away from its average normal.
+fn grow_charts(mesh: &Mesh) -> Vec<Vec<usize>> {
+    // positions with the same value are the same vertex, so that charts grow across seams
+    let mut vertices: HashMap<[u32; 3], u32> = HashMap::new();
+    let vertex_of_position: Vec<u32> = mesh
+        .positions
+        .iter()
+        .map(|p| {
+            let key = [
+                (p[0] + 0.0).to_bits(),
+                (p[1] + 0.0).to_bits(),
+                (p[2] + 0.0).to_bits(),
+            ];
+            let next = vertices.len() as u32;
+            *vertices.entry(key).or_insert(next)
+        })
+        .collect();
+    let vertex = |c: usize| vertex_of_position[mesh.corners[c].position as usize];
+
+    let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for c in 0..mesh.corners.len() {
+        let (a, b) = (vertex(c), vertex(3 * (c / 3) + (c + 1) % 3));
+        edges.entry((a.min(b), a.max(b))).or_default().push(c / 3);
+    }
+    let mut neighbours = vec![Vec::new(); mesh.triangles_count()];
+    for triangles in edges.values() {
+        if let [a, b] = triangles[..] {
+            neighbours[a].push(b);
+            neighbours[b].push(a);
+        }
+    }
+
+    let cos_limit = CHART_ANGLE.to_radians().cos();
+    let mut chart_of = vec![None; mesh.triangles_count()];
+    let mut charts = Vec::new();
+    let mut queue = VecDeque::new();
+    for seed in 0..mesh.triangles_count() {
+        if chart_of[seed].is_some() {
+            continue;
+        }
+        let chart = charts.len();
+        chart_of[seed] = Some(chart);
+        let mut triangles = vec![seed];
+        let mut normal = mesh.face_normal(seed);
+        queue.push_back(seed);
+        while let Some(t) = queue.pop_front() {
+            for &n in neighbours[t].iter() {
+                if chart_of[n].is_some() {
+                    continue;
+                }
+                let face = mesh.face_normal(n);
+                if math::dot(math::normalize(face), math::normalize(normal)) < cos_limit {
+                    continue;
+                }
+                chart_of[n] = Some(chart);
+                triangles.push(n);
+                normal = math::add(normal, face);
+                queue.push_back(n);
+            }
+        }
+        charts.push(triangles);
+    }
+    charts
+}
+
+/// Project the triangles of a chart along their average normal, turned so that the longest direction of the
+/// chart is along u and the rectangle around it stays small.
+fn flatten(mesh: &Mesh, triangles: Vec<usize>) -> Chart {
+    let normal = triangles
+        .iter()
+        .fold([0.0; 3], |sum, &t| math::add(sum, mesh.face_normal(t)));
+    let normal = if math::length(normal) > 0.0 {
+        math::normalize(normal)
+    } else {
+        [0.0, 0.0, 1.0]
+    };
+    let helper = if normal[1].abs() < 0.99 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    // (tangent, bitangent, normal) is right-handed: the chart is seen from the front
+    let tangent = math::normalize(math::cross(helper, normal));
+    let bitangent = math::cross(normal, tangent);
+    let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(3 * triangles.len());
+    for &t in triangles.iter() {
+        for k in 0..3 {
+            let p = mesh.corner_position(3 * t + k);
+            uvs.push([math::dot(p, tangent), math::dot(p, bitangent)]);
+        }
+    }
+
+    // main axis of the corners
+    let count = uvs.len() as f32;
+    let mean = uvs.iter().fold([0.0; 2], |m, uv| {
+        [m[0] + uv[0] / count, m[1] + uv[1] / count]
+    });
+    let (mut xx, mut yy, mut xy) = (0.0, 0.0, 0.0);
+    for uv in uvs.iter() {
+        let (x, y) = (uv[0] - mean[0], uv[1] - mean[1]);
+        xx += x * x;
+        yy += y * y;
+        xy += x * y;
+    }
+    let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+    let (sin, cos) = angle.sin_cos();
+    for uv in uvs.iter_mut() {
+        let (x, y) = (uv[0] - mean[0], uv[1] - mean[1]);
+        *uv = [x * cos + y * sin, y * cos - x * sin];
+    }
+
+    let mut min = [f32::INFINITY; 2];
+    let mut max = [f32::NEG_INFINITY; 2];
+    for uv in uvs.iter() {
+        for k in 0..2 {
+            min[k] = min[k].min(uv[k]);
+            max[k] = max[k].max(uv[k]);
+        }
+    }
+    for uv in uvs.iter_mut() {
+        *uv = [uv[0] - min[0], uv[1] - min[1]];
+    }
+    Chart {
+        triangles,
+        uvs,
+        size: [max[0] - min[0], max[1] - min[1]],
+    }
+}
+
+/// Place the charts in rows, the tallest first, in a square about as large as their total area.
+/// Returns the offset of each chart and the scale bringing the whole into the unit square.
+fn pack(charts: &[Chart]) -> (Vec<[f32; 2]>, f32) {
+    let area: f32 = charts
+        .iter()
+        .map(|chart| chart.size[0] * chart.size[1])
+        .sum();
+    let widest = charts.iter().map(|chart| chart.size[0]).fold(0.0, f32::max);
+    let padding = CHART_PADDING * area.sqrt().max(widest);
+    let padded_area: f32 = charts
+        .iter()
+        .map(|chart| (chart.size[0] + padding) * (chart.size[1] + padding))
+        .sum();
+    let width = padded_area.sqrt().max(widest + padding);
+
+    let mut order: Vec<usize> = (0..charts.len()).collect();
+    order.sort_by(|&a, &b| charts[b].size[1].total_cmp(&charts[a].size[1]));
+    let mut offsets = vec![[0.0; 2]; charts.len()];
+    let (mut x, mut y, mut row_height) = (0.0f32, 0.0f32, 0.0f32);
+    let mut extent = 0.0f32;
+    for i in order {
+        let size = [charts[i].size[0] + padding, charts[i].size[1] + padding];
+        if x > 0.0 && x + size[0] > width {
+            x = 0.0;
+            y += row_height;
+            row_height = 0.0;
+        }
+        offsets[i] = [x + 0.5 * padding, y + 0.5 * padding];
+        x += size[0];
+        row_height = row_height.max(size[1]);
+        extent = extent.max(x).max(y + row_height);
+    }
+    let scale = if extent > 0.0 { 1.0 / extent } else { 1.0 };
+    (offsets, scale)
+}
@@ -0,0 +1,471 @@
+//! Checks of a loaded mesh for the problems that break shading, tangents and tools working on its topology,
+//! and the repair of those that can be fixed without guessing.
+//! Positions are compared by value, so that the edges between duplicated positions are found like shared ones.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use super::math;
+use super::mesh::{Corner, Mesh};
+use super::Submesh;
+
+/// A triangle is degenerate when its area is smaller than this times the square of its longest edge.
+const DEGENERATE_AREA: f32 = 1e-7;
+
+/// The problems found in a mesh, as numbers of elements.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub triangles: usize,
+    pub points: usize,
+    /// triangles and points referencing a position, texture coordinate or normal that doesn't exist
+    pub bad_references: usize,
+    /// triangles without area: with repeated positions or on a line, or with NaN or infinite positions
+    pub degenerate_triangles: usize,
+    /// triangles whose texture coordinates have no area, so they have no tangent
+    pub degenerate_uvs: usize,
+    /// NaN or infinite values
+    pub invalid_positions: usize,
+    pub invalid_uvs: usize,
+    pub invalid_normals: usize,
+    pub invalid_colors: usize,
+    /// positions with the same value as an earlier one
+    pub duplicate_positions: usize,
+    /// edges with a single triangle: holes and the outline of open surfaces
+    pub boundary_edges: usize,
+    /// edges shared by more than two triangles
+    pub non_manifold_edges: usize,
+    /// triangles wound against their neighbours, the fewest of each connected part of the mesh
+    pub flipped_triangles: usize,
+}
+impl Report {
+    /// Whether the mesh has none of the problems fixed by repair. Boundary edges and degenerate texture
+    /// coordinates are common in valid models, they are only reported like non-manifold edges. So are duplicate
+    /// positions: glTF and PLY files split them at the seams of texture coordinates and normals by design,
+    /// even though repair merges them.
+    pub fn is_clean(&self) -> bool {
+        self.bad_references == 0
+            && self.degenerate_triangles == 0
+            && self.invalid_positions == 0
+            && self.invalid_uvs == 0
+            && self.invalid_normals == 0
+            && self.invalid_colors == 0
+            && self.flipped_triangles == 0
+    }
+}
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} triangles, {} points", self.triangles, self.points)?;
+        let lines = [
+            (self.bad_references, "with references out of range"),
+            (self.degenerate_triangles, "degenerate triangles"),
+            (
+                self.degenerate_uvs,
+                "triangles with degenerate texture coordinates",
+            ),
+            (self.invalid_positions, "NaN or infinite positions"),
+            (self.invalid_uvs, "NaN or infinite texture coordinates"),
+            (self.invalid_normals, "NaN or infinite normals"),
+            (self.invalid_colors, "NaN or infinite colors"),
+            (self.duplicate_positions, "duplicate positions"),
+            (self.boundary_edges, "boundary edges"),
+            (self.non_manifold_edges, "non-manifold edges"),
+            (self.flipped_triangles, "triangles with flipped winding"),
+        ];
+        for (count, description) in lines.iter() {
+            if *count > 0 {
+                write!(f, "\n  {} {}", count, description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What repair changed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Repair {
+    pub removed_triangles: usize,
+    pub removed_points: usize,
+    pub merged_positions: usize,
+    pub flipped_triangles: usize,
+    /// NaN or infinite texture coordinates, normals and colors replaced by defaults
+    pub fixed_values: usize,
+}
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "removed {} triangles and {} points, merged {} positions, flipped {} triangles, fixed {} values",
+            self.removed_triangles,
+            self.removed_points,
+            self.merged_positions,
+            self.flipped_triangles,
+            self.fixed_values
+        )
+    }
+}
+
+pub fn validate(mesh: &Mesh) -> Report {
+    Analysis::new(mesh).report
+}
+
+/// Fix the problems found by validate that have a safe fix:
+/// - triangles and points with bad references and degenerate triangles are removed,
+/// - duplicate positions are merged,
+/// - flipped triangles are wound like their neighbours,
+/// - invalid texture coordinates become (0, 0), invalid colors white and corners with invalid normals get
+///   generated ones.
+///
+/// Boundary and non-manifold edges and degenerate texture coordinates are left as they are.
+/// The ranges of `submeshes` follow the removed triangles and points, the submeshes left empty are removed.
+pub fn repair(mesh: &mut Mesh, submeshes: &mut Vec<Submesh>) -> Repair {
+    let analysis = Analysis::new(mesh);
+    let mut repair = Repair::default();
+
+    for uv in mesh.uvs.iter_mut().filter(|uv| !finite(&uv[..])) {
+        *uv = [0.0, 0.0];
+        repair.fixed_values += 1;
+    }
+    for color in mesh.colors.iter_mut().filter(|color| !finite(&color[..])) {
+        *color = [1.0, 1.0, 1.0];
+        repair.fixed_values += 1;
+    }
+    if analysis.report.invalid_normals > 0 {
+        let remap = compact(&mut mesh.normals, |_, n| finite(&n[..]));
+        for corner in mesh.corners.iter_mut().chain(mesh.points.iter_mut()) {
+            if let Some(normal) = corner.normal {
+                // out of range normals are removed with their triangle
+                corner.normal = match remap.get(normal as usize) {
+                    Some(&remapped) => remapped,
+                    None => Some(normal),
+                };
+            }
+        }
+        repair.fixed_values += analysis.report.invalid_normals;
+    }
+
+    for t in 0..mesh.triangles_count() {
+        if analysis.flipped[t] && analysis.keep_triangle[t] {
+            mesh.corners.swap(3 * t + 1, 3 * t + 2);
+            repair.flipped_triangles += 1;
+        }
+    }
+
+    // merge the duplicates into the first position with their value, and drop the invalid positions:
+    // the triangles and points using them are removed below
+    if analysis.report.duplicate_positions > 0 || analysis.report.invalid_positions > 0 {
+        let canonical = &analysis.canonical;
+        let kept: Vec<bool> = mesh
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(p, position)| canonical[p] as usize == p && finite(&position[..]))
+            .collect();
+        let mut remap = compact(&mut mesh.positions, |p, _| kept[p]);
+        if !mesh.colors.is_empty() {
+            compact(&mut mesh.colors, |p, _| kept[p]);
+        }
+        for p in 0..remap.len() {
+            if remap[p].is_none() {
+                remap[p] = remap[canonical[p] as usize];
+            }
+        }
+        for corner in mesh.corners.iter_mut().chain(mesh.points.iter_mut()) {
+            if let Some(&Some(position)) = remap.get(corner.position as usize) {
+                corner.position = position;
+            }
+        }
+        repair.merged_positions = analysis.report.duplicate_positions;
+    }
+
+    let (removed_triangles, removed_points) = remove(
+        mesh,
+        submeshes,
+        &analysis.keep_triangle,
+        &analysis.keep_point,
+    );
+    repair.removed_triangles = removed_triangles;
+    repair.removed_points = removed_points;
+    repair
+}
+
+/// Remove the triangles and points referencing elements that don't exist, which loaders should never make.
+/// Returns how many triangles and points were removed.
+pub fn remove_bad_references(mesh: &mut Mesh, submeshes: &mut Vec<Submesh>) -> (usize, usize) {
+    let keep_triangle: Vec<bool> = mesh
+        .corners
+        .chunks(3)
+        .map(|triangle| triangle.iter().all(|c| valid_corner(mesh, c)))
+        .collect();
+    let keep_point: Vec<bool> = mesh.points.iter().map(|p| valid_corner(mesh, p)).collect();
+    remove(mesh, submeshes, &keep_triangle, &keep_point)
+}
+
+/// Keep the values for which `keep` (taking the index and the value) is true.
+/// Returns the new index of each value, None for those removed.
+fn compact<T, F: Fn(usize, &T) -> bool>(values: &mut Vec<T>, keep: F) -> Vec<Option<u32>> {
+    let mut next = 0;
+    let remap = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            if keep(i, value) {
+                next += 1;
+                Some(next - 1)
+            } else {
+                None
+            }
+        })
+        .collect();
+    let mut i = 0;
+    values.retain(|value| {
+        i += 1;
+        keep(i - 1, value)
+    });
+    remap
+}
+
+fn finite(values: &[f32]) -> bool {
+    values.iter().all(|v| v.is_finite())
+}
+
+fn valid_corner(mesh: &Mesh, corner: &Corner) -> bool {
+    (corner.position as usize) < mesh.positions.len()
+        && corner.uv.map_or(true, |uv| (uv as usize) < mesh.uvs.len())
+        && corner
+            .normal
+            .map_or(true, |normal| (normal as usize) < mesh.normals.len())
+}
+
+/// Keep the triangles and points marked in `keep_triangle` and `keep_point`, moving the ranges of the submeshes
+/// and the polygons.
+fn remove(
+    mesh: &mut Mesh,
+    submeshes: &mut Vec<Submesh>,
+    keep_triangle: &[bool],
+    keep_point: &[bool],
+) -> (usize, usize) {
+    let removed_triangles = keep_triangle.iter().filter(|&&keep| !keep).count();
+    let removed_points = keep_point.iter().filter(|&&keep| !keep).count();
+    if removed_triangles == 0 && removed_points == 0 {
+        return (0, 0);
+    }
+
+    // submeshes count corners then points: kept[i] is the new index of the element i
+    let mut kept = Vec::with_capacity(mesh.corners.len() + mesh.points.len() + 1);
+    let mut next = 0;
+    kept.push(0);
+    for c in 0..mesh.corners.len() {
+        if keep_triangle[c / 3] {
+            next += 1;
+        }
+        kept.push(next);
+    }
+    for &keep in keep_point.iter() {
+        if keep {
+            next += 1;
+        }
+        kept.push(next);
+    }
+    for submesh in submeshes.iter_mut() {
+        let start = kept[submesh.start];
+        submesh.count = kept[submesh.start + submesh.count] - start;
+        submesh.start = start;
+    }
+    submeshes.retain(|submesh| submesh.count > 0);
+
+    // polygons that lost some of their triangles are left as separate triangles
+    if !mesh.polygons.is_empty() {
+        let mut polygons = Vec::with_capacity(mesh.polygons.len());
+        for (first, count) in mesh.polygon_ranges() {
+            let left = (first..first + count).filter(|&t| keep_triangle[t]).count();
+            if left == count {
+                polygons.push(count as u32);
+            } else {
+                polygons.extend(std::iter::repeat(1).take(left));
+            }
+        }
+        mesh.polygons = polygons;
+    }
+
+    let mut t = 0;
+    mesh.corners.retain(|_| {
+        t += 1;
+        keep_triangle[(t - 1) / 3]
+    });
+    let mut t = 0;
+    mesh.smoothing_groups.retain(|_| {
+        t += 1;
+        keep_triangle[t - 1]
+    });
+    let mut p = 0;
+    mesh.points.retain(|_| {
+        p += 1;
+        keep_point[p - 1]
+    });
+    (removed_triangles, removed_points)
+}
+
+/// The report of a mesh and what repair needs to fix it.
+struct Analysis {
+    report: Report,
+    /// first position with the same value as each position
+    canonical: Vec<u32>,
+    keep_triangle: Vec<bool>,
+    keep_point: Vec<bool>,
+    flipped: Vec<bool>,
+}
+impl Analysis {
+    fn new(mesh: &Mesh) -> Analysis {
+        let triangles = mesh.triangles_count();
+        let mut report = Report {
+            triangles,
+            points: mesh.points.len(),
+            ..Report::default()
+        };
+
+        report.invalid_positions = mesh.positions.iter().filter(|p| !finite(&p[..])).count();
+        report.invalid_uvs = mesh.uvs.iter().filter(|uv| !finite(&uv[..])).count();
+        report.invalid_normals = mesh.normals.iter().filter(|n| !finite(&n[..])).count();
+        report.invalid_colors = mesh.colors.iter().filter(|c| !finite(&c[..])).count();
+
+        let mut first: HashMap<[u32; 3], u32> = HashMap::new();
+        let canonical: Vec<u32> = mesh
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                // 0.0 and -0.0 are the same position
+                let key = [
+                    (p[0] + 0.0).to_bits(),
+                    (p[1] + 0.0).to_bits(),
+                    (p[2] + 0.0).to_bits(),
+                ];
+                *first.entry(key).or_insert(i as u32)
+            })
+            .collect();
+        report.duplicate_positions = mesh.positions.len() - first.len();
+
+        let keep_point: Vec<bool> = mesh
+            .points
+            .iter()
+            .map(|point| {
+                valid_corner(mesh, point) && finite(&mesh.positions[point.position as usize][..])
+            })
+            .collect();
+        report.bad_references = mesh
+            .points
+            .iter()
+            .filter(|point| !valid_corner(mesh, point))
+            .count();
+
+        let mut keep_triangle = vec![true; triangles];
+        for (t, keep) in keep_triangle.iter_mut().enumerate() {
+            let corners = &mesh.corners[3 * t..3 * t + 3];
+            if !corners.iter().all(|c| valid_corner(mesh, c)) {
+                report.bad_references += 1;
+                *keep = false;
+                continue;
+            }
+            let ids = [
+                canonical[corners[0].position as usize],
+                canonical[corners[1].position as usize],
+                canonical[corners[2].position as usize],
+            ];
+            let p = [
+                mesh.positions[ids[0] as usize],
+                mesh.positions[ids[1] as usize],
+                mesh.positions[ids[2] as usize],
+            ];
+            let area = math::length(math::cross(math::sub(p[1], p[0]), math::sub(p[2], p[0])));
+            let longest = (0..3)
+                .map(|k| math::length(math::sub(p[(k + 1) % 3], p[k])))
+                .fold(0.0f32, f32::max);
+            if ids[0] == ids[1]
+                || ids[1] == ids[2]
+                || ids[2] == ids[0]
+                || !(area > DEGENERATE_AREA * longest * longest)
+            {
+                report.degenerate_triangles += 1;
+                *keep = false;
+                continue;
+            }
+            if let (Some(a), Some(b), Some(c)) = (corners[0].uv, corners[1].uv, corners[2].uv) {
+                let (a, b, c) = (
+                    mesh.uvs[a as usize],
+                    mesh.uvs[b as usize],
+                    mesh.uvs[c as usize],
+                );
+                let det = (b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1]);
+                // the same test as the tangent generation
+                if !(det.abs() > std::f32::EPSILON * 1e-3) {
+                    report.degenerate_uvs += 1;
+                }
+            }
+        }
+
+        // the triangles on each edge, with whether they go along it from the lower position to the higher one
+        let mut edges: HashMap<(u32, u32), Vec<(usize, bool)>> = HashMap::new();
+        for t in (0..triangles).filter(|&t| keep_triangle[t]) {
+            for k in 0..3 {
+                let a = canonical[mesh.corners[3 * t + k].position as usize];
+                let b = canonical[mesh.corners[3 * t + (k + 1) % 3].position as usize];
+                edges
+                    .entry((a.min(b), a.max(b)))
+                    .or_insert_with(Vec::new)
+                    .push((t, a < b));
+            }
+        }
+        let mut neighbours: Vec<Vec<(usize, bool)>> = vec![Vec::new(); triangles];
+        for sharing in edges.values() {
+            match sharing.len() {
+                1 => report.boundary_edges += 1,
+                2 => {
+                    let ((t0, forward0), (t1, forward1)) = (sharing[0], sharing[1]);
+                    // neighbours wound the same way go along their edge in opposite directions
+                    let consistent = forward0 != forward1;
+                    neighbours[t0].push((t1, consistent));
+                    neighbours[t1].push((t0, consistent));
+                }
+                _ => report.non_manifold_edges += 1,
+            }
+        }
+
+        // orient each connected part from one of its triangles, the triangles against the majority are flipped
+        let mut flipped = vec![false; triangles];
+        let mut visited = vec![false; triangles];
+        for seed in 0..triangles {
+            if visited[seed] || !keep_triangle[seed] {
+                continue;
+            }
+            let mut part = Vec::new();
+            let mut queue = VecDeque::new();
+            visited[seed] = true;
+            queue.push_back(seed);
+            while let Some(t) = queue.pop_front() {
+                part.push(t);
+                for &(neighbour, consistent) in neighbours[t].iter() {
+                    if !visited[neighbour] {
+                        visited[neighbour] = true;
+                        flipped[neighbour] = if consistent { flipped[t] } else { !flipped[t] };
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+            let against = part.iter().filter(|&&t| flipped[t]).count();
+            if 2 * against > part.len() {
+                for &t in part.iter() {
+                    flipped[t] = !flipped[t];
+                }
+            }
+            report.flipped_triangles += part.iter().filter(|&&t| flipped[t]).count();
+        }
+
+        Analysis {
+            report,
+            canonical,
+            keep_triangle,
+            keep_point,
+            flipped,
+        }
+    }
+}
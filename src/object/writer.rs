@@ -0,0 +1,404 @@
+//! Writer of .obj files (and of their .mtl material library), to save a model as it is shown by the viewer.
+//! Positions are shared by the faces like in the loaded mesh, normals are the ones of the vertices, including the
+//! generated ones, and objects, groups and materials are written when they change from a submesh to the next.
+//! Vertex colors are written after the positions as `v x y z r g b`.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::mtl::{Material, TextureMap};
+use super::{obj, Primitive};
+
+/// Write `obj` to the .obj file at `path`.
+/// With `write_materials` the materials go to a .mtl file with the same name, next to it. The textures they use are
+/// referenced where they are, or copied next to the .mtl file with `copy_textures`. Textures embedded in the model
+/// (like in .glb files) have no file of their own and are always written next to the .mtl file.
+pub fn write(obj: &obj, path: &str, write_materials: bool, copy_textures: bool) -> io::Result<()> {
+    let path = Path::new(path);
+    let mtl_path = path.with_extension("mtl");
+    let write_materials = write_materials && !obj.materials.is_empty();
+    let mesh = &obj.mesh;
+
+    // submeshes without a material after ones with a material switch to a default one, `usemtl` needs a name
+    let mut materials = obj.materials.clone();
+    let needs_default = obj
+        .submeshes
+        .iter()
+        .skip_while(|submesh| submesh.material.is_none())
+        .any(|submesh| submesh.material.is_none());
+    let default = if write_materials && needs_default {
+        materials.push(Material::new(&unused_name(&obj.materials, "default")));
+        Some(materials.len() - 1)
+    } else {
+        None
+    };
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# written by ratio")?;
+    if write_materials {
+        writeln!(out, "mtllib {}", file_name(&mtl_path))?;
+    }
+
+    for (i, p) in mesh.positions.iter().enumerate() {
+        match mesh.colors.get(i) {
+            Some(c) => writeln!(
+                out,
+                "v {} {} {} {} {} {}",
+                p[0], p[1], p[2], c[0], c[1], c[2]
+            )?,
+            None => writeln!(out, "v {} {} {}", p[0], p[1], p[2])?,
+        }
+    }
+    for uv in mesh.uvs.iter() {
+        writeln!(out, "vt {} {}", uv[0], uv[1])?;
+    }
+
+    // the normals of the corners, each distinct one written once
+    let mut normals: HashMap<[u32; 3], usize> = HashMap::new();
+    let mut corner_normals = Vec::with_capacity(mesh.corners.len());
+    for n in obj.corner_normals() {
+        let next = normals.len();
+        let index = *normals
+            .entry([n[0].to_bits(), n[1].to_bits(), n[2].to_bits()])
+            .or_insert_with(|| next);
+        if index == next {
+            writeln!(out, "vn {} {} {}", n[0], n[1], n[2])?;
+        }
+        corner_normals.push(index);
+    }
+
+    let mut object = None;
+    let mut group = None;
+    let mut material = None;
+    for submesh in obj.submeshes.iter() {
+        if submesh.object != object {
+            object = submesh.object.clone();
+            writeln!(out, "o {}", object.as_deref().unwrap_or(""))?;
+            // groups belong to the object they are declared in: `o` ends the current one
+            group = None;
+        }
+        if submesh.group != group {
+            group = submesh.group.clone();
+            writeln!(out, "g {}", group.as_deref().unwrap_or(""))?;
+        }
+        if write_materials && submesh.material != material {
+            material = submesh.material;
+            if let Some(m) = material.or(default) {
+                writeln!(out, "usemtl {}", materials[m].name)?;
+            }
+        }
+
+        let range = submesh.start..submesh.start + submesh.count;
+        match submesh.primitive {
+            Primitive::Triangles => {
+                for (t, triangle) in mesh.corners[range].chunks(3).enumerate() {
+                    write!(out, "f")?;
+                    for (k, corner) in triangle.iter().enumerate() {
+                        let normal = corner_normals[submesh.start + 3 * t + k] + 1;
+                        match corner.uv {
+                            Some(uv) => {
+                                write!(out, " {}/{}/{}", corner.position + 1, uv + 1, normal)?
+                            }
+                            None => write!(out, " {}//{}", corner.position + 1, normal)?,
+                        }
+                    }
+                    writeln!(out)?;
+                }
+            }
+            Primitive::Points => {
+                let first = mesh.corners.len();
+                write!(out, "p")?;
+                for point in mesh.points[range.start - first..range.end - first].iter() {
+                    write!(out, " {}", point.position + 1)?;
+                }
+                writeln!(out)?;
+            }
+        }
+    }
+    out.flush()?;
+
+    if write_materials {
+        write_mtl(&materials, &mtl_path, copy_textures)?;
+    }
+    Ok(())
+}
+
+/// `name`, or `name_2`, `name_3`... when a material already has it.
+fn unused_name(materials: &[Material], name: &str) -> String {
+    let taken = |candidate: &str| materials.iter().any(|m| m.name == candidate);
+    let mut candidate = String::from(name);
+    let mut n = 1;
+    while taken(&candidate) {
+        n += 1;
+        candidate = format!("{}_{}", name, n);
+    }
+    candidate
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().into_owned())
+}
+
+fn write_mtl(materials: &[Material], path: &Path, copy_textures: bool) -> io::Result<()> {
+    let folder = path.parent().unwrap_or_else(|| Path::new(""));
+    let stem = path
+        .file_stem()
+        .map_or(String::new(), |stem| stem.to_string_lossy().into_owned());
+    let mut textures = Textures {
+        folder,
+        stem,
+        copy: copy_textures,
+        written: HashMap::new(),
+    };
+
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "# written by ratio")?;
+    for material in materials.iter() {
+        writeln!(out)?;
+        writeln!(out, "newmtl {}", material.name)?;
+        let [r, g, b] = material.diffuse;
+        writeln!(out, "Kd {} {} {}", r, g, b)?;
+        let [r, g, b] = material.specular;
+        writeln!(out, "Ks {} {} {}", r, g, b)?;
+        writeln!(out, "Ns {}", material.shininess)?;
+        writeln!(out, "d {}", material.dissolve)?;
+        writeln!(out, "illum {}", material.illum)?;
+        writeln!(out, "Pm {}", material.metallic)?;
+        writeln!(out, "Pr {}", material.roughness)?;
+        let maps = [
+            ("map_Kd", &material.diffuse_map),
+            ("map_Bump", &material.normal_map),
+            ("map_Ks", &material.specular_map),
+            ("map_d", &material.alpha_map),
+        ];
+        for (keyword, map) in maps.iter() {
+            if let Some(map) = map {
+                writeln!(
+                    out,
+                    "{}{} {}",
+                    keyword,
+                    options(map),
+                    textures.reference(map)?
+                )?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// The options of a texture statement that differ from their default, each with a leading space.
+fn options(map: &TextureMap) -> String {
+    let mut options = String::new();
+    if map.bump_multiplier != 1.0 {
+        options += &format!(" -bm {}", map.bump_multiplier);
+    }
+    if map.offset != [0.0, 0.0, 0.0] {
+        let [u, v, w] = map.offset;
+        options += &format!(" -o {} {} {}", u, v, w);
+    }
+    if map.scale != [1.0, 1.0, 1.0] {
+        let [u, v, w] = map.scale;
+        options += &format!(" -s {} {} {}", u, v, w);
+    }
+    if map.clamp {
+        options += " -clamp on";
+    }
+    options
+}
+
+/// The texture files referenced by a .mtl file being written.
+struct Textures<'a> {
+    folder: &'a Path,
+    stem: String,
+    copy: bool,
+    /// the name given to each texture already written, by source path
+    written: HashMap<PathBuf, String>,
+}
+impl Textures<'_> {
+    /// How `map` is referenced from the .mtl file, writing or copying its image when needed.
+    fn reference(&mut self, map: &TextureMap) -> io::Result<String> {
+        if let Some(name) = self.written.get(&map.path) {
+            return Ok(name.clone());
+        }
+        let name = match &map.data {
+            Some(data) => {
+                let name = format!(
+                    "{}_image{}.{}",
+                    self.stem,
+                    self.written.len(),
+                    image_extension(data)
+                );
+                fs::write(self.folder.join(&name), data.as_slice())?;
+                name
+            }
+            None if self.copy && map.path.is_file() => {
+                let name = file_name(&map.path);
+                let target = self.folder.join(&name);
+                if !same_file(&map.path, &target) {
+                    fs::copy(&map.path, &target)?;
+                }
+                name
+            }
+            None => {
+                if self.copy {
+                    println!(
+                        "WARN: texture '{}' not found, it is referenced but not copied",
+                        map.path.display()
+                    );
+                }
+                match map.path.strip_prefix(self.folder) {
+                    Ok(relative) => relative.to_string_lossy().into_owned(),
+                    Err(_) => fs::canonicalize(&map.path)
+                        .unwrap_or_else(|_| map.path.clone())
+                        .to_string_lossy()
+                        .into_owned(),
+                }
+            }
+        };
+        self.written.insert(map.path.clone(), name.clone());
+        Ok(name)
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Extension for an encoded image, recognized from its first bytes.
+fn image_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "png"
+    } else if data.starts_with(&[0xFF, 0xD8]) {
+        "jpg"
+    } else {
+        "bin"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::super::{obj, Primitive};
+
+    const MODEL: &str = "mtllib box.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 0.5 2
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+vn 0 0 -1
+o box
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/4/1
+g back
+usemtl blue
+f 1/1/2 4/4/2 3/3/2
+o lid
+g back
+f 3/3/2 4/4/2 5/1/2
+o loose
+usemtl none
+f 2/2/2 3/3/2 5//1
+p 5 1
+";
+
+    const LIBRARY: &str = "newmtl red
+Kd 1 0 0
+newmtl blue
+Kd 0 0 1
+";
+
+    fn library(name: &str) -> io::Result<Vec<u8>> {
+        match name {
+            "box.mtl" => Ok(LIBRARY.as_bytes().to_vec()),
+            _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+        }
+    }
+
+    /// Everything that must survive a round trip, with the indices resolved to values.
+    fn summary(obj: &obj) -> (Vec<String>, Vec<String>) {
+        let normals = obj.corner_normals();
+        let mesh = &obj.mesh;
+        let mut corners: Vec<String> = (0..mesh.corners.len())
+            .map(|c| {
+                format!(
+                    "{:?} {:?} {:?}",
+                    mesh.corner_position(c),
+                    mesh.corner_uv(c),
+                    normals[c]
+                )
+            })
+            .collect();
+        corners.extend(
+            mesh.points
+                .iter()
+                .map(|point| format!("{:?}", mesh.positions[point.position as usize])),
+        );
+        let submeshes = obj
+            .submeshes
+            .iter()
+            .map(|submesh| {
+                format!(
+                    "{} {:?} {:?} {} {}",
+                    submesh.name(),
+                    submesh.material.map(|m| &obj.materials[m].name),
+                    submesh.primitive,
+                    submesh.start,
+                    submesh.count
+                )
+            })
+            .collect();
+        (corners, submeshes)
+    }
+
+    #[test]
+    fn round_trip() {
+        let model = obj::from_bytes(
+            MODEL.as_bytes(),
+            "box.obj",
+            &library,
+            true,
+            true,
+            true,
+            false,
+        )
+        .unwrap();
+        assert!(model
+            .submeshes
+            .iter()
+            .any(|submesh| submesh.primitive == Primitive::Points));
+
+        let folder = std::env::temp_dir().join(format!("ratio_writer_{}", std::process::id()));
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("box_export.obj");
+        let path = path.to_str().unwrap();
+        model.save(path, true, false).unwrap();
+        let reloaded = obj::load_new(path, true, true, true, false);
+        std::fs::remove_dir_all(&folder).unwrap();
+        let reloaded = reloaded.unwrap();
+
+        assert_eq!(reloaded.mesh.positions, model.mesh.positions);
+        let (corners, submeshes) = summary(&model);
+        let (reloaded_corners, reloaded_submeshes) = summary(&reloaded);
+        assert_eq!(reloaded_corners, corners);
+        // the faces without a material get the default one written to the library
+        let submeshes: Vec<String> = submeshes
+            .iter()
+            .map(|submesh| submesh.replace(" None ", " Some(\"default\") "))
+            .collect();
+        assert_eq!(reloaded_submeshes, submeshes);
+    }
+}
@@ -2,6 +2,7 @@ use super::gl;
 use gl::types::{GLenum, GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use std::mem::size_of;
 
+use super::shaders::Program;
 use super::textures::{Texture, TextureColorFormat, TextureDataType};
 
 pub struct VertexBuffer {
@@ -148,6 +149,62 @@ impl VertexBufferLayout {
         }
     }
 
+    /// Like new, but every attribute is read by the input of `program` with the same name, wherever the linker put it,
+    /// starting `offset` bytes into vertices of `stride` bytes: (name, type, offset).
+    /// The inputs named in `defaults` are not in the vertices, they read the given constant value instead.
+    /// The VertexBuffer holding the vertices must be bound.
+    pub fn for_program(
+        attributes: Vec<(String, AttributeType, usize)>,
+        stride: usize,
+        defaults: &[(String, [f32; 4])],
+        program: &Program,
+        gl: &gl::Gl,
+    ) -> VertexBufferLayout {
+        let location = |name: &str| {
+            let cname = std::ffi::CString::new(name)
+                .unwrap_or_else(|_| panic!("Error getting CString from: {}", name));
+            unsafe { gl.GetAttribLocation(program.get_id(), cname.as_ptr()) }
+        };
+
+        let mut vao: GLuint = 0;
+        unsafe {
+            gl.GenVertexArrays(1, &mut vao);
+            gl.BindVertexArray(vao);
+
+            for (name, attribute_type, offset) in attributes.iter() {
+                let location = location(name);
+                if location == -1 {
+                    println!("WARN: Attribute '{}' is not used by the shader.", name);
+                    continue;
+                }
+                gl.VertexAttribPointer(
+                    location as GLuint,
+                    attribute_type.size(),
+                    attribute_type.gl_type(),
+                    gl::FALSE,
+                    stride as GLsizei,
+                    *offset as _,
+                );
+                gl.EnableVertexAttribArray(location as GLuint);
+            }
+            for (name, value) in defaults.iter() {
+                let location = location(name);
+                if location != -1 {
+                    gl.DisableVertexAttribArray(location as GLuint);
+                    gl.VertexAttrib4f(location as GLuint, value[0], value[1], value[2], value[3]);
+                }
+            }
+        }
+
+        VertexBufferLayout {
+            id: vao,
+            attributes: attributes
+                .into_iter()
+                .map(|(name, attribute_type, _)| (name, attribute_type))
+                .collect(),
+        }
+    }
+
     pub fn get_id(&self) -> u32 {
         self.id
     }
@@ -170,7 +227,7 @@ pub struct IndexBuffer {
     indices_count: usize,
 }
 impl IndexBuffer {
-    pub fn new(indices: Vec<u32>, gl: &gl::Gl) -> IndexBuffer {
+    pub fn new(indices: &[u32], gl: &gl::Gl) -> IndexBuffer {
         let mut ib: GLuint = 0;
         unsafe {
             gl.GenBuffers(1, &mut ib);
@@ -204,6 +261,25 @@ impl IndexBuffer {
     pub fn get_indices_count(&self) -> usize {
         self.indices_count
     }
+
+    pub fn delete(&self, gl: &gl::Gl) {
+        unsafe {
+            gl.DeleteBuffers(1, &self.id);
+        }
+    }
+
+    pub fn update_data(&mut self, indices: &[u32], gl: &gl::Gl) {
+        unsafe {
+            gl.BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.id);
+            gl.BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                (indices.len() * size_of::<u32>()) as GLsizeiptr,
+                indices.as_ptr() as *const GLvoid,
+                gl::STATIC_DRAW,
+            );
+        }
+        self.indices_count = indices.len();
+    }
 }
 
 pub struct FrameBuffer {
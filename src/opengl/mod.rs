@@ -9,7 +9,7 @@ pub mod uniforms;
 
 use glutin::{self, PossiblyCurrent};
 
-use gl::types::{GLint, GLsizei};
+use gl::types::{GLenum, GLint, GLsizei};
 use std::ffi::CStr;
 
 use textures::Texture;
@@ -66,15 +66,15 @@ impl Glwrapper {
         }
     }
 
-    pub fn draw_elements(&self, indices_to_draw: i32) {
+    /// Draw `count` indices starting from the index number `first` of the bound IndexBuffer,
+    /// as the primitives of `mode` (gl::TRIANGLES, gl::POINTS...).
+    pub fn draw_elements_range(&self, mode: GLenum, first: usize, count: usize) {
         unsafe {
-            self.gl.ClearColor(0.8, 0.1, 0.3, 1.0);
-            self.gl.Clear(gl::COLOR_BUFFER_BIT);
             self.gl.DrawElements(
-                gl::TRIANGLES,
-                indices_to_draw as GLsizei,
+                mode,
+                count as GLsizei,
                 gl::UNSIGNED_INT,
-                std::ptr::null() as *const gl::types::GLvoid,
+                (first * std::mem::size_of::<u32>()) as *const gl::types::GLvoid,
             );
         }
     }
@@ -48,7 +48,8 @@ impl Texture {
         data_type: TextureDataType,
         gl: &gl::Gl,
     ) -> Texture {
-        let pixels = match data {
+        // borrow the data so that it lives until TexImage2D has copied it
+        let pixels = match &data {
             Some(d) => d.as_ptr() as *const std::ffi::c_void,
             None => std::ptr::null(),
         };
@@ -85,7 +86,30 @@ impl Texture {
     }
 
     pub fn load_new(path: &str, id_counter: u32, gl: &gl::Gl) -> Texture {
-        let img = image::open(&std::path::Path::new(path)).unwrap();
+        Self::try_load_new(path, id_counter, gl).unwrap()
+    }
+
+    /// Like load_new, but returns an error instead of panicking when the image can't be read.
+    pub fn try_load_new(
+        path: &str,
+        id_counter: u32,
+        gl: &gl::Gl,
+    ) -> Result<Texture, image::ImageError> {
+        let img = image::open(&std::path::Path::new(path))?;
+        Ok(Self::from_image(img, id_counter, gl))
+    }
+
+    /// Like try_load_new, for an encoded image (PNG, JPEG...) already in memory.
+    pub fn try_load_from_memory(
+        bytes: &[u8],
+        id_counter: u32,
+        gl: &gl::Gl,
+    ) -> Result<Texture, image::ImageError> {
+        let img = image::load_from_memory(bytes)?;
+        Ok(Self::from_image(img, id_counter, gl))
+    }
+
+    fn from_image(img: image::DynamicImage, id_counter: u32, gl: &gl::Gl) -> Texture {
         let img_size = img.dimensions();
         let data = img.to_bytes();
 
@@ -48,6 +48,9 @@ impl Uniform {
             UniformType::Texture(tex_id) => unsafe {
                 gl.Uniform1i(location, tex_id as i32); // The docs says to use Uniform1i or Uniform1iv.
             },
+            UniformType::Bool(v) => unsafe {
+                gl.Uniform1i(location, v as i32);
+            },
             UniformType::Mat4x4(v) => unsafe {
                 gl.UniformMatrix4fv(location, 1, gl::FALSE, &v[0]);
             },
@@ -94,6 +97,9 @@ impl Uniform {
             UniformType::Texture(tex_id) => unsafe {
                 gl.Uniform1i(location, tex_id as i32);
             },
+            UniformType::Bool(v) => unsafe {
+                gl.Uniform1i(location, v as i32);
+            },
             UniformType::Mat4x4(v) => unsafe {
                 gl.UniformMatrix4fv(location, 1, gl::FALSE, &v[0]);
             },